@@ -0,0 +1,63 @@
+#![allow(unused)]
+
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+
+/// Something that runs closures, so code can be generic over the pool it runs on.
+pub trait Executor {
+    /// Returned by `submit`, yields the closure's value.
+    type Handle<T: Send + 'static>: Join<T>;
+
+    /// Runs `f` without a way to learn its outcome.
+    fn execute<F>(&self, f: F)
+    where F: FnOnce() + Send + 'static;
+
+    /// Runs `f`, its value is taken from the returned handle.
+    fn submit<F, T>(&self, f: F) -> Self::Handle<T>
+    where F: FnOnce() -> T + Send + 'static, T: Send + 'static;
+
+    /// Stops the executor and waits for its threads to exit.
+    fn shutdown(self)
+    where Self: Sized;
+}
+
+/// Handle to a closure submitted to an `Executor`.
+pub trait Join<T> {
+    type Error: std::fmt::Debug;
+
+    /// Blocks until the closure finished and returns its value.
+    fn join(self) -> Result<T, Self::Error>;
+}
+
+/// Runs every closure right away on the calling thread, e.g. for deterministic tests.
+/// A panic in an `execute`d closure reaches the caller, `submit` hands it to the handle instead.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InlineExecutor;
+
+/// The already known outcome of a closure run by `InlineExecutor`.
+#[derive(Debug)]
+pub struct Ready<T>(Result<T, Box<dyn Any + Send>>);
+
+impl<T> Join<T> for Ready<T> {
+    type Error = Box<dyn Any + Send>;
+
+    fn join(self) -> Result<T, Self::Error> {
+        self.0
+    }
+}
+
+impl Executor for InlineExecutor {
+    type Handle<T: Send + 'static> = Ready<T>;
+
+    fn execute<F>(&self, f: F)
+    where F: FnOnce() + Send + 'static {
+        f();
+    }
+
+    fn submit<F, T>(&self, f: F) -> Ready<T>
+    where F: FnOnce() -> T + Send + 'static, T: Send + 'static {
+        Ready(panic::catch_unwind(AssertUnwindSafe(f)))
+    }
+
+    fn shutdown(self) {}
+}
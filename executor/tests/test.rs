@@ -0,0 +1,26 @@
+use executor::{Executor, InlineExecutor, Join};
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+#[test]
+fn test_inline_executor() {
+    let executor = InlineExecutor;
+    let counter = Arc::new(AtomicUsize::new(0));
+
+    // 任务在调用线程上立即执行
+    let counter_clone = counter.clone();
+    executor.execute(move || {
+        counter_clone.fetch_add(1, Ordering::SeqCst);
+    });
+    assert_eq!(counter.load(Ordering::SeqCst), 1);
+
+    let caller = std::thread::current().id();
+    assert!(executor.submit(move || std::thread::current().id() == caller).join().unwrap());
+
+    // panic 交给句柄
+    let result = executor.submit(|| -> usize { panic!("task panicked!") }).join();
+    assert_eq!(result.unwrap_err().downcast_ref::<&str>(), Some(&"task panicked!"));
+
+    executor.shutdown();
+}
@@ -1,15 +1,107 @@
 #![allow(unused)]
 
+use std::any::Any;
 use std::collections::VecDeque;
-use std::sync::{atomic, Arc, Condvar, Mutex};
-use std::time::Duration;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{atomic, mpsc, Arc, Condvar, Mutex, RwLock};
+use std::time::{Duration, Instant};
 use std::{process, thread};
 
+pub use executor::{Executor, InlineExecutor, Join};
+
 pub struct ThreadPool {
-    executing: Arc<atomic::AtomicBool>,
     size: usize,
-    workers: VecDeque<Option<Worker>>,
-    jobs: Arc<Mutex<VecDeque<Job>>>,
+    shared: Arc<Shared>,
+    drop_policy: DropPolicy,
+}
+
+/// What happens to queued jobs when the pool is dropped or terminated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DropPolicy {
+    /// The workers run every queued job before exiting.
+    #[default]
+    Drain,
+    /// Queued jobs are dropped without running, running jobs still finish.
+    Discard,
+    /// Like `Drain`, but jobs still queued after the given time are discarded.
+    DrainWithDeadline(Duration),
+}
+
+/// What happened to the jobs of a pool, returned by `ThreadPool::terminate`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TerminateReport {
+    pub ran: usize,
+    pub discarded: usize,
+    pub panicked: usize,
+}
+
+/// Handle to a job submitted with `ThreadPool::spawn`.
+pub struct JobHandle<T> {
+    result: mpsc::Receiver<Result<T, Box<dyn Any + Send>>>,
+}
+
+/// Why a spawned job produced no value.
+#[derive(Debug)]
+pub enum JoinError {
+    /// The job panicked, with the payload it panicked with.
+    Panicked(Box<dyn Any + Send>),
+    /// The job was discarded by the pool's drop policy before it ran.
+    Discarded,
+}
+
+impl<T> JobHandle<T> {
+    /// Blocks until the job finished and returns its value.
+    pub fn join(self) -> Result<T, JoinError> {
+        match self.result.recv() {
+            Ok(result) => result.map_err(JoinError::Panicked),
+            Err(_) => Err(JoinError::Discarded),
+        }
+    }
+}
+
+impl<T> Join<T> for JobHandle<T> {
+    type Error = JoinError;
+
+    fn join(self) -> Result<T, JoinError> {
+        JobHandle::join(self)
+    }
+}
+
+// state shared between the pool and its workers
+struct Shared {
+    executing: atomic::AtomicBool,
+    jobs: Mutex<VecDeque<Job>>,
+    // notified when a job is pushed or the pool stops executing
+    job_available: Condvar,
+    // notified when a worker takes the last queued job after the pool stopped executing
+    drained: Condvar,
+    // a worker that dies pushes its replacement here
+    workers: Mutex<Vec<Worker>>,
+    panic_handler: RwLock<Option<PanicHandler>>,
+    ran: atomic::AtomicUsize,
+    panicked: atomic::AtomicUsize,
+    // jobs queued or running, `idle` is notified when it drops to zero
+    in_flight: Mutex<usize>,
+    idle: Condvar,
+}
+
+impl Shared {
+    fn finished(&self, jobs: usize) {
+        let mut in_flight = self.in_flight.lock().expect("mutex poisoned.");
+        *in_flight -= jobs;
+        if *in_flight == 0 {
+            self.idle.notify_all();
+        }
+    }
+}
+
+// marks a job finished once it's done, even if the panic handler panics
+struct Finished<'a>(&'a Shared);
+
+impl Drop for Finished<'_> {
+    fn drop(&mut self) {
+        self.0.finished(1);
+    }
 }
 
 struct Worker {
@@ -17,38 +109,81 @@ struct Worker {
 }
 
 type Task = Box<dyn FnOnce() + Send + 'static>;
+type Payload = Box<dyn Any + Send>;
+type PanicHandler = Arc<dyn Fn(Payload) + Send + Sync>;
 
 struct Job {
     task: Task,
+    // receives the payload instead of the pool's panic handler
+    on_panic: Option<Box<dyn FnOnce(Payload) + Send>>,
 }
 
 impl Job {
     pub fn new(task: Task) -> Self {
-        Self { task }
+        Self { task, on_panic: None }
+    }
+
+    fn run(self, shared: &Shared) {
+        let _finished = Finished(shared);
+        match panic::catch_unwind(AssertUnwindSafe(self.task)) {
+            Ok(()) => {
+                shared.ran.fetch_add(1, atomic::Ordering::Relaxed);
+            },
+            Err(payload) => {
+                shared.panicked.fetch_add(1, atomic::Ordering::Relaxed);
+                if let Some(on_panic) = self.on_panic {
+                    on_panic(payload);
+                } else {
+                    let handler = shared.panic_handler.read().expect("rwlock poisoned.").clone();
+                    if let Some(handler) = handler {
+                        handler(payload);
+                    }
+                }
+            },
+        }
     }
+}
+
+// replaces its worker if the worker thread dies, e.g. from a panicking panic handler
+struct Sentinel {
+    shared: Arc<Shared>,
+}
 
-    fn run(self) {
-        (self.task)();
+impl Drop for Sentinel {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            let worker = Worker::new(self.shared.clone());
+            self.shared.workers.lock().expect("mutex poisoned.").push(worker);
+        }
     }
 }
 
 impl Worker {
-    fn new(pool_jobs: Arc<Mutex<VecDeque<Job>>>, pool_alive: Arc<atomic::AtomicBool>) -> Self {
-        let thread = thread::spawn(move || loop {
-            let job = {
-                let mut pool_jobs = pool_jobs.lock().expect("mutex poisoned.");
-                if !pool_alive.load(atomic::Ordering::Acquire)
-                && pool_jobs.is_empty() {
-                    break;
+    fn new(shared: Arc<Shared>) -> Self {
+        let thread = thread::spawn(move || {
+            let _sentinel = Sentinel { shared: shared.clone() };
+            loop {
+                let job = {
+                    let mut jobs = shared.jobs.lock().expect("mutex poisoned.");
+                    loop {
+                        if let Some(job) = jobs.pop_front() {
+                            if jobs.is_empty() && !shared.executing.load(atomic::Ordering::Acquire) {
+                                shared.drained.notify_all();
+                            }
+                            break Some(job);
+                        }
+                        if !shared.executing.load(atomic::Ordering::Acquire) {
+                            break None;
+                        }
+                        jobs = shared.job_available.wait(jobs).expect("mutex poisoned.");
+                    }
+                };
+                match job {
+                    Some(job) => job.run(&shared),
+                    None => break,
                 }
-                pool_jobs.pop_front()
-            };
-            if let Some(job) = job {
-                job.run();
-            } else {
-                thread::sleep(Duration::from_micros(0));
             }
-            });
+        });
         Self {
             thread,
         }
@@ -57,59 +192,163 @@ impl Worker {
 
 impl ThreadPool {
     pub fn new(size: usize) -> Self {
-        let executing = Arc::new(atomic::AtomicBool::new(true));
+        let shared = Arc::new(Shared {
+            executing: atomic::AtomicBool::new(true),
+            jobs: Mutex::new(VecDeque::new()),
+            job_available: Condvar::new(),
+            drained: Condvar::new(),
+            workers: Mutex::new(Vec::with_capacity(size)),
+            panic_handler: RwLock::new(None),
+            ran: atomic::AtomicUsize::new(0),
+            panicked: atomic::AtomicUsize::new(0),
+            in_flight: Mutex::new(0),
+            idle: Condvar::new(),
+        });
 
-        let jobs = Arc::new(Mutex::new(VecDeque::new()));
-
-        let mut workers = VecDeque::with_capacity(size);
         for _ in 0..size {
-            workers.push_back(Some(Worker::new(jobs.clone(), executing.clone())));
+            let worker = Worker::new(shared.clone());
+            shared.workers.lock().expect("mutex poisoned.").push(worker);
         }
 
         Self {
-            executing,
             size,
-            workers,
-            jobs,
+            shared,
+            drop_policy: DropPolicy::default(),
         }
     }
 
+    /// Sets what happens to queued jobs when the pool goes away, `Drain` by default.
+    pub fn drop_policy(mut self, policy: DropPolicy) -> Self {
+        self.drop_policy = policy;
+        self
+    }
+
+    /// Called with the payload of every panicking job submitted with `execute`.
+    pub fn panic_handler<H>(self, handler: H) -> Self
+    where H: Fn(Box<dyn Any + Send>) + Send + Sync + 'static {
+        *self.shared.panic_handler.write().expect("rwlock poisoned.") = Some(Arc::new(handler));
+        self
+    }
+
+    /// Number of jobs that panicked so far.
+    pub fn panicked_jobs(&self) -> usize {
+        self.shared.panicked.load(atomic::Ordering::Relaxed)
+    }
+
     pub fn execute<F>(&self, task: F)
     where F : FnOnce() + Send + 'static {
-        match self.jobs.lock() {
+        self.push(Job::new(Box::new(task)));
+    }
+
+    /// Like `execute`, but the returned handle yields the job's value or its panic payload.
+    pub fn spawn<F, T>(&self, f: F) -> JobHandle<T>
+    where F: FnOnce() -> T + Send + 'static, T: Send + 'static {
+        let (sender, receiver) = mpsc::channel();
+        let panic_sender = sender.clone();
+        // the handle may have been dropped, nobody wants the result then
+        self.push(Job {
+            task: Box::new(move || { let _ = sender.send(Ok(f())); }),
+            on_panic: Some(Box::new(move |payload| { let _ = panic_sender.send(Err(payload)); })),
+        });
+        JobHandle { result: receiver }
+    }
+
+    /// Blocks until the queue is empty and no worker is running a job.
+    /// Never returns when called from a job of this pool.
+    pub fn wait_idle(&self) {
+        let in_flight = self.shared.in_flight.lock().expect("mutex poisoned.");
+        let _idle = self.shared.idle.wait_while(in_flight, |in_flight| *in_flight > 0).expect("mutex poisoned.");
+    }
+
+    /// Like `wait_idle`, but gives up after `timeout`. Returns whether the pool became idle.
+    pub fn wait_idle_timeout(&self, timeout: Duration) -> bool {
+        let in_flight = self.shared.in_flight.lock().expect("mutex poisoned.");
+        let (_idle, result) = self.shared.idle
+            .wait_timeout_while(in_flight, timeout, |in_flight| *in_flight > 0)
+            .expect("mutex poisoned.");
+        !result.timed_out()
+    }
+
+    fn push(&self, job: Job) {
+        *self.shared.in_flight.lock().expect("mutex poisoned.") += 1;
+        match self.shared.jobs.lock() {
             Ok(mut jobs) => {
-                jobs.push_back(Job::new(Box::new(task)));
+                jobs.push_back(job);
             },
             Err(_) => panic!("mutex poisoned."),
         };
+        self.shared.job_available.notify_one();
     }
 
-    pub fn terminate(self) {}
-}
+    /// Shuts the pool down according to its drop policy and waits for the workers to exit.
+    pub fn terminate(mut self) -> TerminateReport {
+        self.stop()
+    }
 
-impl Drop for ThreadPool {
-    fn drop(&mut self) {
-        self.executing.store(false, atomic::Ordering::Release);
+    fn stop(&mut self) -> TerminateReport {
+        let mut discarded = Vec::new();
+        {
+            // flipped under the lock so a worker can't miss the wakeup between its check and wait
+            let mut jobs = self.shared.jobs.lock().expect("mutex poisoned.");
+            self.shared.executing.store(false, atomic::Ordering::Release);
+            self.shared.job_available.notify_all();
+
+            match self.drop_policy {
+                DropPolicy::Drain => (),
+                DropPolicy::Discard => discarded.extend(jobs.drain(..)),
+                DropPolicy::DrainWithDeadline(timeout) => {
+                    let (mut jobs, _) = self.shared.drained
+                        .wait_timeout_while(jobs, timeout, |jobs| !jobs.is_empty())
+                        .expect("mutex poisoned.");
+                    discarded.extend(jobs.drain(..));
+                },
+            }
+        }
 
-        let mut etask = vec![];
+        self.shared.finished(discarded.len());
+
+        // a worker dying meanwhile pushes a replacement, join until none are left
         loop {
-            let job = { self.jobs.lock().expect("mutex poisoned.").pop_front() };
-            match job {
-                Some(task) => etask.push(thread::spawn(move || task.run())),
+            let worker = self.shared.workers.lock().expect("mutex poisoned.").pop();
+            match worker {
+                Some(worker) => { let _ = worker.thread.join(); },
                 None => break,
-            };
-        }
-        for task in etask {
-            task.join();
+            }
         }
 
-        for worker in &mut self.workers {
-            match worker.take().unwrap().thread.join() {
-                Ok(()) => (),
-                Err(_) => {
-                    eprintln!("some thread has already exited with panic");
-                }
-            }
+        // without workers nothing drains the queue, whatever is left was never run
+        let left = discarded.len();
+        discarded.extend(self.shared.jobs.lock().expect("mutex poisoned.").drain(..));
+        self.shared.finished(discarded.len() - left);
+
+        TerminateReport {
+            ran: self.shared.ran.load(atomic::Ordering::Relaxed),
+            discarded: discarded.len(),
+            panicked: self.shared.panicked.load(atomic::Ordering::Relaxed),
         }
     }
-}
\ No newline at end of file
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl Executor for ThreadPool {
+    type Handle<T: Send + 'static> = JobHandle<T>;
+
+    fn execute<F>(&self, f: F)
+    where F: FnOnce() + Send + 'static {
+        ThreadPool::execute(self, f);
+    }
+
+    fn submit<F, T>(&self, f: F) -> JobHandle<T>
+    where F: FnOnce() -> T + Send + 'static, T: Send + 'static {
+        self.spawn(f)
+    }
+
+    fn shutdown(self) {
+        self.terminate();
+    }
+}
@@ -1,4 +1,4 @@
-use simple_one::ThreadPool;
+use simple_one::{DropPolicy, Executor, InlineExecutor, Join, JoinError, TerminateReport, ThreadPool};
 
 use std::sync::atomic::{self, AtomicUsize, Ordering};
 use std::sync::{Arc, Barrier};
@@ -15,13 +15,14 @@ fn test_thread_pool_basic() {
         counter_clone.fetch_add(1, Ordering::SeqCst);
     });
 
-    thread::sleep(Duration::from_millis(100)); // 等待任务执行完成
+    pool.wait_idle(); // 等待任务执行完成
     assert_eq!(counter.load(Ordering::SeqCst), 1);
 }
 
 #[test]
 fn test_thread_pool_concurrent_tasks() {
-    let pool = ThreadPool::new(4);
+    // 任务由池中已有的工作线程执行, 线程数需足够让所有任务同时等待屏障
+    let pool = ThreadPool::new(10);
     let counter = Arc::new(AtomicUsize::new(0));
     let barrier = Arc::new(Barrier::new(10));
 
@@ -81,7 +82,7 @@ fn test_thread_pool_large_number_of_tasks() {
         });
     }
 
-    thread::sleep(Duration::from_millis(500)); // 等待所有任务执行完成
+    pool.wait_idle(); // 等待所有任务执行完成
     assert_eq!(counter.load(Ordering::SeqCst), 1000);
 }
 
@@ -227,4 +228,220 @@ fn test_performance() {
 
     assert_eq!(counter.load(atomic::Ordering::SeqCst), 10000);
     println!("Time elapsed: {:?}", duration);
-}
\ No newline at end of file
+}
+
+// 读取某个线程已消耗的 CPU 时间 (utime + stime, 单位为时钟周期)
+#[cfg(target_os = "linux")]
+fn thread_cpu_ticks(tid: &str) -> u64 {
+    let stat = std::fs::read_to_string(format!("/proc/self/task/{}/stat", tid)).unwrap();
+    // 跳过可能包含空格的 "(comm)" 字段, 之后第 12/13 个字段是 utime/stime
+    let fields: Vec<&str> = stat[stat.rfind(')').unwrap() + 1..].split_whitespace().collect();
+    fields[11].parse::<u64>().unwrap() + fields[12].parse::<u64>().unwrap()
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_idle_pool_does_not_spin() {
+    let pool = ThreadPool::new(2);
+
+    // 在工作线程上取得它的线程 id
+    let (sender, receiver) = std::sync::mpsc::channel();
+    pool.execute(move || {
+        let thread_self = std::fs::read_link("/proc/thread-self").unwrap();
+        sender.send(thread_self.file_name().unwrap().to_str().unwrap().to_string()).unwrap();
+    });
+    let tid = receiver.recv().unwrap();
+    thread::sleep(Duration::from_millis(50));
+
+    let before = thread_cpu_ticks(&tid);
+    thread::sleep(Duration::from_millis(500));
+    let after = thread_cpu_ticks(&tid);
+
+    // 空闲的工作线程应当休眠, 几乎不消耗 CPU
+    assert!(after - before <= 2, "idle worker used {} ticks", after - before);
+}
+
+// 提交一个占住唯一工作线程的任务, 等它开始运行后再排入 10 个任务
+fn pool_with_queued_jobs(policy: DropPolicy, counter: &Arc<AtomicUsize>) -> ThreadPool {
+    let pool = ThreadPool::new(1).drop_policy(policy);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    pool.execute(move || {
+        sender.send(()).unwrap();
+        thread::sleep(Duration::from_millis(200));
+    });
+    receiver.recv().unwrap();
+
+    for _ in 0..10 {
+        let counter_clone = counter.clone();
+        pool.execute(move || {
+            counter_clone.fetch_add(1, Ordering::SeqCst);
+        });
+    }
+    pool
+}
+
+#[test]
+fn test_terminate_report() {
+    let counter = Arc::new(AtomicUsize::new(0));
+    let pool = pool_with_queued_jobs(DropPolicy::Drain, &counter);
+    pool.execute(|| panic!("task panicked!"));
+
+    let report = pool.terminate();
+    assert_eq!(report, TerminateReport { ran: 11, discarded: 0, panicked: 1 });
+    assert_eq!(counter.load(Ordering::SeqCst), 10);
+}
+
+#[test]
+fn test_drop_policy_discard() {
+    let counter = Arc::new(AtomicUsize::new(0));
+    let pool = pool_with_queued_jobs(DropPolicy::Discard, &counter);
+
+    // 正在运行的任务会完成, 排队的任务被丢弃
+    let report = pool.terminate();
+    assert_eq!(report, TerminateReport { ran: 1, discarded: 10, panicked: 0 });
+    assert_eq!(counter.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn test_drop_policy_drain_with_deadline() {
+    let counter = Arc::new(AtomicUsize::new(0));
+    let pool = pool_with_queued_jobs(DropPolicy::DrainWithDeadline(Duration::from_millis(20)), &counter);
+
+    // 工作线程在期限内一直被占用, 排队的任务来不及执行
+    drop(pool);
+    assert_eq!(counter.load(Ordering::SeqCst), 0);
+
+    let pool = ThreadPool::new(2).drop_policy(DropPolicy::DrainWithDeadline(Duration::from_secs(5)));
+    for _ in 0..10 {
+        let counter_clone = counter.clone();
+        pool.execute(move || {
+            counter_clone.fetch_add(1, Ordering::SeqCst);
+        });
+    }
+    assert_eq!(pool.terminate().discarded, 0);
+    assert_eq!(counter.load(Ordering::SeqCst), 10);
+}
+
+#[test]
+fn test_spawn_join() {
+    let pool = ThreadPool::new(2);
+
+    let handles: Vec<_> = (0..10).map(|i| pool.spawn(move || i * 2)).collect();
+    let results: Vec<_> = handles.into_iter().map(|handle| handle.join().unwrap()).collect();
+    assert_eq!(results, (0..10).map(|i| i * 2).collect::<Vec<_>>());
+
+    // panic 的负载会交给 join 的调用者
+    let handle = pool.spawn(|| -> usize { panic!("task panicked!") });
+    match handle.join() {
+        Err(JoinError::Panicked(payload)) => assert_eq!(payload.downcast_ref::<&str>(), Some(&"task panicked!")),
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    // panic 被捕获, 工作线程仍然可用
+    assert_eq!(pool.spawn(|| "still alive").join().unwrap(), "still alive");
+}
+
+#[test]
+fn test_spawn_discarded() {
+    let pool = ThreadPool::new(1).drop_policy(DropPolicy::Discard);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    pool.execute(move || {
+        sender.send(()).unwrap();
+        thread::sleep(Duration::from_millis(100));
+    });
+    receiver.recv().unwrap();
+
+    let handle = pool.spawn(|| 1);
+    drop(pool);
+    assert!(matches!(handle.join(), Err(JoinError::Discarded)));
+}
+
+#[test]
+fn test_panic_handler() {
+    let payloads = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let payloads_clone = payloads.clone();
+    let pool = ThreadPool::new(1).panic_handler(move |payload| {
+        let message = payload.downcast_ref::<&str>().unwrap().to_string();
+        payloads_clone.lock().unwrap().push(message);
+    });
+
+    pool.execute(|| panic!("first"));
+    pool.execute(|| panic!("second"));
+    // spawn 提交的任务把负载交给句柄, 不经过 panic_handler
+    assert!(pool.spawn(|| -> usize { panic!("third") }).join().is_err());
+
+    // 唯一的工作线程没有因 panic 而退出
+    assert_eq!(pool.spawn(|| 1).join().unwrap(), 1);
+    assert_eq!(pool.panicked_jobs(), 3);
+    assert_eq!(*payloads.lock().unwrap(), vec!["first", "second"]);
+}
+
+#[test]
+fn test_dead_worker_respawned() {
+    // panic_handler 本身 panic 会杀死工作线程, 线程池应补上新的工作线程
+    let pool = ThreadPool::new(1).panic_handler(|_| panic!("handler panicked!"));
+    pool.execute(|| panic!("task panicked!"));
+
+    assert_eq!(pool.spawn(|| 1).join().unwrap(), 1);
+    let report = pool.terminate();
+    assert_eq!(report, TerminateReport { ran: 1, discarded: 0, panicked: 1 });
+}
+
+#[test]
+fn test_wait_idle() {
+    let pool = ThreadPool::new(2);
+    let counter = Arc::new(AtomicUsize::new(0));
+
+    // 空闲的线程池立即返回
+    assert!(pool.wait_idle_timeout(Duration::ZERO));
+
+    for _ in 0..4 {
+        let counter_clone = counter.clone();
+        pool.execute(move || {
+            thread::sleep(Duration::from_millis(100));
+            counter_clone.fetch_add(1, Ordering::SeqCst);
+        });
+    }
+    pool.execute(|| panic!("task panicked!"));
+
+    assert!(!pool.wait_idle_timeout(Duration::from_millis(10)));
+    pool.wait_idle();
+    assert_eq!(counter.load(Ordering::SeqCst), 4);
+
+    // 线程池在等待后仍可继续使用
+    assert_eq!(pool.spawn(|| 1).join().unwrap(), 1);
+    assert!(pool.wait_idle_timeout(Duration::from_secs(5)));
+}
+
+#[test]
+fn test_terminate_without_workers() {
+    let pool = ThreadPool::new(0);
+    pool.execute(|| ());
+    let handle = pool.spawn(|| 1);
+
+    // 没有工作线程, 排队的任务不会执行, 计为丢弃
+    let report = pool.terminate();
+    assert_eq!(report, TerminateReport { ran: 0, discarded: 2, panicked: 0 });
+    assert!(matches!(handle.join(), Err(JoinError::Discarded)));
+}
+
+// 与具体执行器无关的代码
+fn sum_squares<E: Executor>(executor: &E, n: usize) -> usize {
+    let handles: Vec<_> = (0..n).map(|i| executor.submit(move || i * i)).collect();
+    handles.into_iter().map(|handle| handle.join().unwrap()).sum()
+}
+
+#[test]
+fn test_executor_trait() {
+    let pool = ThreadPool::new(4);
+    assert_eq!(sum_squares(&pool, 10), 285);
+    assert_eq!(sum_squares(&InlineExecutor, 10), 285);
+
+    let counter = Arc::new(AtomicUsize::new(0));
+    let counter_clone = counter.clone();
+    Executor::execute(&pool, move || {
+        counter_clone.fetch_add(1, Ordering::SeqCst);
+    });
+    Executor::shutdown(pool);
+    assert_eq!(counter.load(Ordering::SeqCst), 1);
+}
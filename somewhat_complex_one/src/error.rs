@@ -1,12 +1,117 @@
 #![allow(unused)]
 
+use std::{fmt, sync::Arc, time::Duration};
+
+use crate::{TaskMeta, TaskState};
+
 #[derive(Debug, PartialEq)]
+#[non_exhaustive]
 pub enum Error {
     Empty,
-    Cancelled,
-    Timeout,
+    Cancelled(ErrorContext),
+    Timeout(ErrorContext),
     MultipleWaits,
-    ChannelDisconnected,
-    CancelAfterRunning,
+    /// The task's result can never arrive, e.g. because the task panicked.
+    ChannelDisconnected(ErrorContext),
+    CancelAfterRunning(ErrorContext),
+    WouldDeadlock,
+    Rejected,
+    DeadlineMissed(ErrorContext),
+    /// See `ThreadPool::commit_with_state`.
+    NoWorkerState,
     Other(String),
-}
\ No newline at end of file
+}
+
+/// The task an error is about, and where it stood at the time.
+/// Compared without `elapsed`, which differs between any two errors.
+#[derive(Debug, Clone)]
+pub struct ErrorContext {
+    pub task_id: u64,
+    pub label: Option<Arc<str>>,
+    pub state: TaskState,
+    /// Time since the task was committed.
+    pub elapsed: Duration,
+}
+
+impl ErrorContext {
+    pub(crate) fn new(meta: &TaskMeta, state: TaskState) -> Self {
+        Self {
+            task_id: meta.id,
+            label: meta.label.clone(),
+            state,
+            elapsed: meta.submitted_at.elapsed(),
+        }
+    }
+}
+
+impl PartialEq for ErrorContext {
+    fn eq(&self, other: &Self) -> bool {
+        self.task_id == other.task_id && self.label == other.label && self.state == other.state
+    }
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "task {}", self.task_id)?;
+        if let Some(label) = &self.label {
+            write!(f, " ({})", label)?;
+        }
+        write!(f, ", {:?} after {:?}", self.state, self.elapsed)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Empty => write!(f, "task has nothing to run"),
+            Error::Cancelled(context) => write!(f, "task cancelled: {}", context),
+            Error::Timeout(context) => write!(f, "task timed out: {}", context),
+            Error::MultipleWaits => write!(f, "task already waited for"),
+            Error::ChannelDisconnected(context) => write!(f, "task result lost: {}", context),
+            Error::CancelAfterRunning(context) => write!(f, "task already running, can't cancel: {}", context),
+            Error::WouldDeadlock => write!(f, "waiting would deadlock the pool"),
+            Error::Rejected => write!(f, "task rejected by the pool"),
+            Error::DeadlineMissed(context) => write!(f, "task missed its deadline: {}", context),
+            Error::NoWorkerState => write!(f, "pool has no worker state of that type"),
+            Error::Other(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Outcome of a failed task committed with `ThreadPool::commit_fallible`.
+#[derive(Debug, PartialEq)]
+pub enum TaskError<E> {
+    /// The task returned `Err(e)`.
+    Failed(E),
+    Panicked,
+    Cancelled,
+    Timeout,
+    /// Any other error of the pool, e.g. a rejection.
+    Pool(Error),
+}
+
+impl<E> From<Error> for TaskError<E> {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::Cancelled(_) => TaskError::Cancelled,
+            Error::Timeout(_) => TaskError::Timeout,
+            error => TaskError::Pool(error),
+        }
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for TaskError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TaskError::Failed(error) => write!(f, "task failed: {}", error),
+            TaskError::Panicked => write!(f, "task panicked"),
+            TaskError::Cancelled => write!(f, "task cancelled"),
+            TaskError::Timeout => write!(f, "task timed out"),
+            TaskError::Pool(error) => error.fmt(f),
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> std::error::Error for TaskError<E> {}
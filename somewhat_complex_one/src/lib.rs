@@ -1,3 +1,5 @@
+#![allow(clippy::module_inception, clippy::new_ret_no_self)]
+
 mod pool;
 mod task;
 mod sheduler;
@@ -6,4 +8,5 @@ mod error;
 pub use pool::*;
 pub use sheduler::*;
 pub use task::*;
-pub use error::*;
\ No newline at end of file
+pub use error::*;
+pub use executor::{Executor, InlineExecutor, Join};
\ No newline at end of file
@@ -0,0 +1,86 @@
+#![allow(unused)]
+
+use std::{collections::HashSet, fs, thread};
+
+/// Which cores the workers of a pool are pinned to, see `ThreadPoolBuilder::affinity`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CoreSelection {
+    /// Worker `i` is pinned to `cores[i % cores.len()]`.
+    Cores(Vec<usize>),
+    /// Like `RoundRobin`, but only over one logical core per physical core,
+    /// so two workers never share a core through hyper-threading.
+    PhysicalCores,
+    /// Workers are spread over all cores the process may run on, in turn.
+    RoundRobin,
+}
+
+impl CoreSelection {
+    /// The cores workers are pinned to in turn, empty if none could be determined.
+    pub(crate) fn resolve(&self) -> Vec<usize> {
+        match self {
+            CoreSelection::Cores(cores) => cores.clone(),
+            CoreSelection::RoundRobin => available_cores(),
+            CoreSelection::PhysicalCores => {
+                let mut seen = HashSet::new();
+                available_cores()
+                    .into_iter()
+                    .filter(|&core| physical_core(core).is_none_or(|physical| seen.insert(physical)))
+                    .collect()
+            },
+        }
+    }
+}
+
+// cores in the process' affinity mask, e.g. "0-3,8" in /proc/self/status
+fn available_cores() -> Vec<usize> {
+    let allowed = fs::read_to_string("/proc/self/status").ok().and_then(|status| {
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix("Cpus_allowed_list:"))
+            .map(|list| parse_core_list(list.trim()))
+    });
+
+    match allowed {
+        Some(cores) if !cores.is_empty() => cores,
+        _ => (0..thread::available_parallelism().map_or(1, |n| n.get())).collect(),
+    }
+}
+
+fn parse_core_list(list: &str) -> Vec<usize> {
+    list.split(',')
+        .filter_map(|range| match range.split_once('-') {
+            Some((first, last)) => Some(first.parse().ok()?..=last.parse().ok()?),
+            None => range.parse().ok().map(|core| core..=core),
+        })
+        .flatten()
+        .collect()
+}
+
+// (package, core) a logical core belongs to
+fn physical_core(core: usize) -> Option<(usize, usize)> {
+    let topology = format!("/sys/devices/system/cpu/cpu{}/topology", core);
+    let read = |name: &str| fs::read_to_string(format!("{}/{}", topology, name)).ok()?.trim().parse().ok();
+    Some((read("physical_package_id")?, read("core_id")?))
+}
+
+/// Pins the calling thread to `core`, returns false if that didn't work.
+#[cfg(target_os = "linux")]
+pub(crate) fn pin_current_thread(core: usize) -> bool {
+    unsafe extern "C" {
+        fn sched_setaffinity(pid: i32, cpusetsize: usize, mask: *const u64) -> i32;
+    }
+
+    // same size as glibc's cpu_set_t
+    let mut mask = [0u64; 16];
+    if core >= mask.len() * 64 {
+        return false;
+    }
+    mask[core / 64] |= 1 << (core % 64);
+    // pid 0 is the calling thread
+    unsafe { sched_setaffinity(0, std::mem::size_of_val(&mask), mask.as_ptr()) == 0 }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn pin_current_thread(_core: usize) -> bool {
+    false
+}
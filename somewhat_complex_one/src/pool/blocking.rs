@@ -0,0 +1,72 @@
+#![allow(unused)]
+
+use std::{panic::{self, AssertUnwindSafe}, sync::{atomic, Arc, Mutex}, thread};
+
+use crate::{sheduler::{FifoScheduler, NextTask, Scheduler}, AsTask};
+
+/// Secondary threads for tasks that block, grown on demand up to `max_threads`,
+/// so blocking calls never occupy the pool's regular workers.
+pub(crate) struct BlockingPool {
+    scheduler: Arc<FifoScheduler>,
+    max_threads: usize,
+    threads: Mutex<Vec<thread::JoinHandle<()>>>,
+    idle: Arc<atomic::AtomicUsize>,
+}
+
+impl BlockingPool {
+    pub(crate) fn new(max_threads: usize) -> Self {
+        Self {
+            scheduler: Arc::new(FifoScheduler::new()),
+            max_threads,
+            threads: Mutex::new(Vec::new()),
+            idle: Arc::new(atomic::AtomicUsize::new(0)),
+        }
+    }
+
+    pub(crate) fn schedule(&self, task: Box<dyn AsTask>) {
+        {
+            // grow when every idle thread already has a queued task to pick up
+            let mut threads = self.threads.lock().expect("mutex poisoned.");
+            if threads.len() < self.max_threads
+                && self.scheduler.queued() >= self.idle.load(atomic::Ordering::Acquire) {
+                threads.push(self.spawn());
+            }
+        }
+        self.scheduler.schedule(task);
+    }
+
+    fn spawn(&self) -> thread::JoinHandle<()> {
+        let scheduler = self.scheduler.clone();
+        let idle = self.idle.clone();
+        thread::spawn(move || loop {
+            idle.fetch_add(1, atomic::Ordering::AcqRel);
+            let task = scheduler.next_task();
+            idle.fetch_sub(1, atomic::Ordering::AcqRel);
+            match task {
+                // a panicking task loses its result, not the thread counted in `max_threads`
+                NextTask::Task(task) => { panic::catch_unwind(AssertUnwindSafe(|| task.run())).ok(); },
+                NextTask::Idle => continue,
+                NextTask::Shutdown => break,
+            }
+        })
+    }
+
+    pub(crate) fn threads(&self) -> usize {
+        self.threads.lock().expect("mutex poisoned.").len()
+    }
+
+    pub(crate) fn idle(&self) -> usize {
+        self.idle.load(atomic::Ordering::Acquire)
+    }
+
+    pub(crate) fn queued(&self) -> usize {
+        self.scheduler.queued()
+    }
+
+    pub(crate) fn terminate(&self) {
+        self.scheduler.terminate();
+        for thread in self.threads.lock().expect("mutex poisoned.").drain(..) {
+            thread.join().ok();
+        }
+    }
+}
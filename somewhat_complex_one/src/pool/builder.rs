@@ -1,14 +1,25 @@
 #![allow(unused)]
 
-use std::sync::Arc;
+use std::{any::{Any, TypeId}, sync::{atomic, Arc, OnceLock}};
 
-use crate::{pool::*, sheduler::{FifoScheduler, Scheduler}};
+use crate::{pool::*, sheduler::{FifoScheduler, Scheduler, SchedulerLayer}};
 
-use super::{pool::ThreadPool, worker::Worker};
+use super::{affinity::CoreSelection, rejection::{RejectionHandler, RejectionPolicy}};
+
+use super::{pool::ThreadPool, shared::{Shared, WorkerState}, worker::Worker};
+
+static NEXT_POOL_ID: atomic::AtomicUsize = atomic::AtomicUsize::new(0);
 
 pub struct ThreadPoolBuilder {
     size: usize,
     scheduler: Option<Arc<dyn Scheduler>>,
+    layers: Vec<Box<dyn SchedulerLayer>>,
+    replace_stuck_workers: bool,
+    queue_capacity: Option<usize>,
+    rejection_handler: Box<dyn RejectionHandler>,
+    max_blocking_threads: usize,
+    affinity: Option<CoreSelection>,
+    worker_state: Option<WorkerState>,
 }
 
 impl ThreadPoolBuilder {
@@ -16,6 +27,13 @@ impl ThreadPoolBuilder {
         Self {
             size: DEFAULT_POOL_SIZE,
             scheduler: None,
+            layers: Vec::new(),
+            replace_stuck_workers: false,
+            queue_capacity: None,
+            rejection_handler: Box::new(RejectionPolicy::default()),
+            max_blocking_threads: DEFAULT_MAX_BLOCKING_THREADS,
+            affinity: None,
+            worker_state: None,
         }
     }
 
@@ -34,21 +52,126 @@ impl ThreadPoolBuilder {
         }
     }
 
+    /// Wraps the scheduler in `layer`. Layers are applied in the order they are added,
+    /// so the last one added sees tasks first.
+    pub fn layer<L>(mut self, layer: L) -> Self
+    where L: SchedulerLayer + 'static {
+        self.layers.push(Box::new(layer));
+        self
+    }
+
+    /// Pins each worker to a core. Only supported on Linux, a no-op elsewhere.
+    pub fn affinity(self, selection: CoreSelection) -> Self {
+        Self {
+            affinity: Some(selection),
+            ..self
+        }
+    }
+
+    /// Gives each worker a state created by `init` with the worker's id, on the worker's
+    /// own thread. Tasks committed through `ThreadPool::commit_with_state` borrow it mutably.
+    pub fn worker_state<S, F>(self, init: F) -> Self
+    where
+        S: 'static,
+        F: Fn(usize) -> S + Send + Sync + 'static {
+        Self {
+            worker_state: Some(WorkerState {
+                type_id: TypeId::of::<S>(),
+                init: Box::new(move |id| Box::new(init(id))),
+                teardown: None,
+            }),
+            ..self
+        }
+    }
+
+    /// Called with the worker's id and state when a worker exits.
+    /// Must come after `worker_state` with the same `S`, it's ignored otherwise.
+    pub fn worker_state_teardown<S, F>(mut self, teardown: F) -> Self
+    where
+        S: 'static,
+        F: Fn(usize, S) + Send + Sync + 'static {
+        if let Some(state) = &mut self.worker_state
+            && state.type_id == TypeId::of::<S>() {
+            state.teardown = Some(Box::new(move |id, state: Box<dyn Any>| {
+                if let Ok(state) = state.downcast::<S>() {
+                    teardown(id, *state);
+                }
+            }));
+        }
+        self
+    }
+
+    /// Whether a worker stuck on a timed-out task is replaced by a fresh one.
+    /// The stuck worker exits once its task returns, so the pool keeps its size.
+    pub fn replace_stuck_workers(self, replace: bool) -> Self {
+        Self {
+            replace_stuck_workers: replace,
+            ..self
+        }
+    }
+
+    /// Bounds the number of queued tasks, commits beyond it go to the rejection handler.
+    /// Only applies to schedulers reporting `Scheduler::queued`.
+    pub fn queue_capacity(self, capacity: usize) -> Self {
+        Self {
+            queue_capacity: Some(capacity),
+            ..self
+        }
+    }
+
+    /// Handles commits to a full queue or a pool that is shutting down, `RejectionPolicy::Abort` by default.
+    pub fn rejection_policy<H>(self, handler: H) -> Self
+    where H: RejectionHandler + 'static {
+        Self {
+            rejection_handler: Box::new(handler),
+            ..self
+        }
+    }
+
+    /// Upper limit for the threads spawned on demand by `ThreadPool::commit_blocking`,
+    /// `build` fails if it's 0.
+    pub fn max_blocking_threads(self, max: usize) -> Self {
+        Self {
+            max_blocking_threads: max,
+            ..self
+        }
+    }
+
+    #[allow(clippy::result_unit_err)]
     pub fn build(mut self) -> Result<ThreadPool, ()> {
-        if self.size > MAX_POOL_SIZE {
+        // without a blocking thread, `commit_blocking` tasks would never run
+        if self.size > MAX_POOL_SIZE
+            || self.max_blocking_threads == 0
+            || self.max_blocking_threads > MAX_POOL_SIZE {
             return Err(());
         }
 
         if self.scheduler.is_none() {
             self.scheduler = Some(Arc::new(FifoScheduler::new()));
         }
+        let scheduler = self.layers
+            .iter()
+            .fold(self.scheduler.unwrap(), |scheduler, layer| layer.layer(scheduler));
+
+        let mut shared = Shared::new(
+            NEXT_POOL_ID.fetch_add(1, atomic::Ordering::Relaxed),
+            self.size,
+            scheduler,
+        );
+        shared.replace_stuck_workers = self.replace_stuck_workers;
+        shared.cores = self.affinity.map(|selection| selection.resolve()).unwrap_or_default();
+        shared.queue_capacity = self.queue_capacity;
+        shared.rejection_handler = self.rejection_handler;
+        shared.worker_state = self.worker_state;
 
-        let mut workers = Vec::with_capacity(self.size);
-        (0..self.size).for_each(|id| workers.push(Worker::new(id, self.scheduler.as_ref().unwrap().clone())));
+        let shared = Arc::new(shared);
+        (0..self.size).for_each(|_| shared.spawn_worker());
 
         Ok(ThreadPool {
-            scheduler: self.scheduler.unwrap(),
-            workers,
+            shared,
+            delayed: OnceLock::new(),
+            blocking: OnceLock::new(),
+            max_blocking_threads: self.max_blocking_threads,
         })
     }
 }
\ No newline at end of file
@@ -0,0 +1,53 @@
+#![allow(unused)]
+
+use std::sync::{Arc, Mutex};
+
+use crate::{task::Task, Error, TaskHandle, TaskState, ToTask};
+
+use super::ThreadPool;
+
+/// A named batch of tasks that can be cancelled and waited for as a whole.
+pub struct TaskGroup<'a, T> {
+    pool: &'a ThreadPool,
+    name: Arc<str>,
+    handles: Mutex<Vec<TaskHandle<T>>>,
+}
+
+impl<'a, T: Send + 'static> TaskGroup<'a, T> {
+    pub(super) fn new(pool: &'a ThreadPool, name: &str) -> Self {
+        Self {
+            pool,
+            name: name.into(),
+            handles: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn commit(&self, task: impl ToTask<T>) {
+        let mut task = Task::new(task);
+        task.meta.group = Some(self.name.clone());
+        let handle = self.pool.commit(task);
+        self.handles.lock().expect("mutex poisoned.").push(handle);
+    }
+
+    /// Cancels every pending task of the group and asks running ones to stop through `is_cancelled`.
+    pub fn cancel_all(&self) {
+        for handle in self.handles.lock().expect("mutex poisoned.").iter() {
+            match handle.state() {
+                // the task may have started since its state was read
+                TaskState::Pending if handle.cancel().is_err() => handle.request_stop(),
+                TaskState::Running => handle.request_stop(),
+                _ => (),
+            }
+        }
+    }
+
+    /// Waits for every task of the group, results are in commit order.
+    pub fn wait_all(self) -> Vec<Result<T, Error>> {
+        let handles = self.handles.into_inner().expect("mutex poisoned.");
+        handles.iter().map(|handle| handle.wait()).collect()
+    }
+}
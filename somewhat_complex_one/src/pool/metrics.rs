@@ -0,0 +1,18 @@
+/// A snapshot of what a pool is doing, taken by `ThreadPool::metrics`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PoolMetrics {
+    /// Regular workers, including replacements for stuck ones.
+    pub workers: usize,
+    /// Regular workers currently running a task.
+    pub active_workers: usize,
+    /// Tasks waiting for a regular worker.
+    pub queued: usize,
+    /// Threads spawned so far for `ThreadPool::commit_blocking`.
+    pub blocking_workers: usize,
+    /// Blocking threads currently running a task.
+    pub active_blocking_workers: usize,
+    /// Tasks waiting for a blocking thread.
+    pub blocking_queued: usize,
+    /// Tasks run by a regular worker that ended as `TaskState::Failed`.
+    pub failed: usize,
+}
@@ -3,8 +3,21 @@
 mod pool;
 mod worker;
 mod builder;
+mod shared;
+mod rejection;
+mod blocking;
+mod metrics;
+mod group;
+mod affinity;
 
 pub(crate) const MAX_POOL_SIZE: usize = 128;
 pub(crate) const DEFAULT_POOL_SIZE: usize = 4;
+pub(crate) const DEFAULT_MAX_BLOCKING_THREADS: usize = 16;
+pub(crate) use shared::{Blocked, InFlightTasks};
+pub(crate) use worker::{current_pool, current_worker};
+pub use affinity::CoreSelection;
+pub use group::TaskGroup;
+pub use metrics::PoolMetrics;
+pub use rejection::{RejectionHandler, RejectionPolicy};
 pub use builder::ThreadPoolBuilder as ThreadPoolBuilder;
 pub use pool::ThreadPool as ThreadPool;
\ No newline at end of file
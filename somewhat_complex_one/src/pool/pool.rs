@@ -1,16 +1,26 @@
 #![allow(unused)]
 
 use std::{
+    any::TypeId,
     collections::{HashMap, VecDeque}, 
-    sync::{mpsc, Arc, Condvar, Mutex}
+    sync::{atomic, mpsc, Arc, Condvar, Mutex, OnceLock},
+    thread,
+    time::{Duration, Instant},
 };
 
-use crate::{sheduler::{FifoScheduler, Scheduler}, task::{Task, ToTask}, TaskHandle};
-use super::worker::Worker;
+use executor::Executor;
+
+use crate::{sheduler::{lend_slots, DelayQueue, FifoScheduler, NextTask, Scheduler}, task::{fallible_task, FallibleHandle, Period, RecurringHandle, RecurringRun, RetryPolicy, RetryTask, Task, Timeout, ToTask}, AsTask, Error, TaskHandle};
+use super::{blocking::BlockingPool, shared::Shared, worker::{self, StateTask, Worker}, PoolMetrics, TaskGroup};
+
+// how long a worker waiting in `join` looks for other work before checking on `b` again
+const JOIN_POLL_INTERVAL: Duration = Duration::from_millis(1);
 
 pub struct ThreadPool {
-    pub(super) scheduler: Arc<dyn Scheduler>,
-    pub(super) workers: Vec<Worker>,
+    pub(super) shared: Arc<Shared>,
+    pub(super) delayed: OnceLock<Arc<DelayQueue>>, // started on first delayed commit
+    pub(super) blocking: OnceLock<BlockingPool>, // started on first blocking commit
+    pub(super) max_blocking_threads: usize,
     //task_registry: Arc<Mutex<(HashMap<u16, Arc<Task>>, Condvar)>>,
     //task_queue: Arc<(Mutex<VecDeque<Task>>, Condvar)>,
 }
@@ -21,25 +31,253 @@ impl ThreadPool {
     }
 
     pub fn commit<T: Send + 'static>(&self, task: impl ToTask<T>) -> TaskHandle<T> {
+        let (task, handle) = self.prepare(task);
+        self.submit(Box::new(task)).ok();
+        handle
+    }
+
+    /// Commits a task on behalf of `tenant`, for schedulers sharing the pool between tenants
+    /// such as `FairScheduler`.
+    pub fn commit_for<T: Send + 'static>(&self, tenant: &str, task: impl ToTask<T>) -> TaskHandle<T> {
+        let mut task = Task::new(task);
+        task.meta.tenant = Some(tenant.into());
+        self.commit(task)
+    }
+
+    /// Commits a task that should start before `deadline`, for deadline-aware schedulers
+    /// such as `EdfScheduler`.
+    pub fn commit_with_deadline<T: Send + 'static>(&self, deadline: Instant, task: impl ToTask<T>) -> TaskHandle<T> {
+        let mut task = Task::new(task);
+        task.meta.deadline = Some(deadline);
+        self.commit(task)
+    }
+
+    /// Like `commit`, but fails if the rejection handler refused the task.
+    pub fn try_commit<T: Send + 'static>(&self, task: impl ToTask<T>) -> Result<TaskHandle<T>, Error> {
+        let (task, handle) = self.prepare(task);
+        self.submit(Box::new(task))?;
+        Ok(handle)
+    }
+
+    /// Starts a named batch of tasks sharing cancellation and waiting.
+    pub fn group<T: Send + 'static>(&self, name: &str) -> TaskGroup<'_, T> {
+        TaskGroup::new(self, name)
+    }
+
+    /// Commits a task that blocks, e.g. on file I/O. It runs on a separate set of threads
+    /// grown on demand, so it never holds up tasks committed through `commit`.
+    pub fn commit_blocking<T: Send + 'static>(&self, task: impl ToTask<T>) -> TaskHandle<T> {
+        let (task, mut handle) = self.prepare(task);
+        // no worker runs it, so a worker waiting on it can't deadlock the pool
+        handle.pool_id = None;
+        self.blocking_pool().schedule(self.shared.track(Box::new(task)));
+        handle
+    }
+
+    /// Commits a task that stays `Pending` until `delay` has elapsed.
+    pub fn commit_after<T: Send + 'static>(&self, delay: Duration, task: impl ToTask<T>) -> TaskHandle<T> {
+        self.commit_at(Instant::now() + delay, task)
+    }
+
+    /// Commits a task that stays `Pending` until `instant`, then goes through the queue
+    /// like a task committed with `commit`. It can be cancelled through its handle like
+    /// any other pending task.
+    pub fn commit_at<T: Send + 'static>(&self, instant: Instant, task: impl ToTask<T>) -> TaskHandle<T> {
+        let (task, handle) = self.prepare(task);
+        self.delay_queue().schedule_at(instant, self.shared.track(Box::new(task)));
+        handle
+    }
+
+    /// Runs `job` now and then repeatedly according to `period` until the returned handle
+    /// cancels it or the pool shuts down. Errors returned by `job` don't stop the schedule,
+    /// a run the pool rejects, e.g. because its queue is full, does.
+    pub fn commit_periodic<F, E>(&self, period: Period, job: F) -> RecurringHandle
+    where
+        F: FnMut() -> Result<(), E> + Send + 'static,
+        E: ToString {
+        let (run, handle) = RecurringRun::new(period, job, self.delay_queue().clone());
+        self.delay_queue().schedule_at(run.due(), Box::new(run));
+        handle
+    }
+
+    /// Commits a fallible job that is retried according to `policy`.
+    /// Retries are delayed on the pool's timer and go through the scheduler again,
+    /// the handle resolves with the first success or the last error.
+    pub fn commit_with_retry<T, E, F>(&self, policy: RetryPolicy<E>, job: F) -> TaskHandle<Result<T, E>>
+    where
+        T: Send + 'static,
+        E: ToString + Send + 'static,
+        F: FnMut() -> Result<T, E> + Send + 'static {
+        let (task, handle) = self.prepare(Task::from_future(None));
+        let task = RetryTask::new(task, job, policy, self.delay_queue().clone(), self.shared.in_flight());
+        self.submit(Box::new(task)).ok();
+        handle
+    }
+
+    /// Commits a task that borrows the state of the worker running it, see
+    /// `ThreadPoolBuilder::worker_state`. Fails with `Error::NoWorkerState` if the pool
+    /// has no worker state of type `S`, or if the task would run where the state isn't
+    /// available, e.g. on the committing thread under `RejectionPolicy::CallerRuns`.
+    pub fn commit_with_state<S, T, F>(&self, f: F) -> TaskHandle<T>
+    where
+        S: 'static,
+        T: Send + 'static,
+        F: FnOnce(&mut S) -> T + Send + 'static {
+        let has_state = self.shared.worker_state.as_ref().is_some_and(|state| state.type_id == TypeId::of::<S>());
+        let (task, handle) = self.prepare(move || worker::with_state(f));
+        if has_state {
+            self.submit(Box::new(StateTask::<T, S>::new(task))).ok();
+        } else {
+            task.abort(Error::NoWorkerState);
+        }
+        handle
+    }
+
+    /// Commits a job whose `Err(e)` or panic ends the task as `TaskState::Failed`
+    /// and is counted in `PoolMetrics::failed`. The handle returns `TaskError::Failed(e)`.
+    pub fn commit_fallible<T, E, F>(&self, job: F) -> FallibleHandle<T, E>
+    where
+        T: Send + 'static,
+        E: Send + 'static,
+        F: FnOnce() -> Result<T, E> + Send + 'static {
+        FallibleHandle::new(self.commit(fallible_task(job)))
+    }
+
+    /// Commits a task that may run for at most `timeout` once started.
+    /// Past the deadline its handle resolves with `Error::Timeout` and the task is asked
+    /// to stop through `is_cancelled`; see `ThreadPoolBuilder::replace_stuck_workers`.
+    pub fn commit_with_timeout<T: Send + 'static>(&self, timeout: Duration, task: impl ToTask<T>) -> TaskHandle<T> {
+        let mut task = Task::new(task);
+        task.meta.timeout = Some(timeout);
+        self.commit(task)
+    }
+
+    fn submit(&self, task: Box<dyn AsTask>) -> Result<(), Error> {
+        self.shared.submit(self.shared.track(task))
+    }
+
+    fn prepare<T: Send + 'static>(&self, task: impl ToTask<T>) -> (Task<T>, TaskHandle<T>) {
         let (sender, receiver) = mpsc::channel();
         
         let mut task = Task::new(task);
         task.result_sender = Some(sender);
+        task.pool_id = Some(self.shared.id);
+        task.meta.submitted_at = Instant::now();
+        if let Some(timeout) = task.meta.timeout {
+            task.timeout = Some(Timeout::new(timeout, self.delay_queue().clone()));
+        }
         let handle = TaskHandle::new(&task, receiver);
+        (task, handle)
+    }
 
-        self.scheduler.schedule(Box::new(task));
-        handle
+    fn blocking_pool(&self) -> &BlockingPool {
+        self.blocking.get_or_init(|| BlockingPool::new(self.max_blocking_threads))
+    }
+
+    fn delay_queue(&self) -> &Arc<DelayQueue> {
+        self.delayed.get_or_init(|| {
+            let shared = self.shared.clone();
+            Arc::new(DelayQueue::new(move |task| { shared.submit(task).ok(); }))
+        })
+    }
+
+    /// Runs `a` on the calling thread and `b` on the pool, then waits for `b`.
+    /// When called from a worker of this pool, the caller keeps executing queued
+    /// tasks while waiting instead of blocking, so recursive joins can't starve the pool.
+    pub fn join<A, B, RA, RB>(&self, a: A, b: B) -> (RA, Result<RB, Error>)
+    where
+        A: FnOnce() -> RA,
+        B: FnOnce() -> RB + Send + 'static,
+        RB: Send + 'static {
+        let handle = self.commit(b);
+        let result_a = a();
+
+        if worker::current_pool().is_some_and(|pool| pool.id == self.shared.id) {
+            // until `b` has a result, or never will, e.g. because it panicked
+            let result_b = lend_slots(|| loop {
+                if let Some(result_b) = handle.try_wait() {
+                    break result_b;
+                }
+                if let NextTask::Task(task) = self.shared.scheduler.next_task_timeout(JOIN_POLL_INTERVAL) {
+                    task.run();
+                }
+            });
+            return (result_a, result_b);
+        }
+
+        (result_a, handle.wait())
+    }
+
+    pub fn metrics(&self) -> PoolMetrics {
+        let shared = &self.shared;
+        let mut metrics = PoolMetrics {
+            workers: shared.workers.lock().expect("mutex poisoned.").len(),
+            active_workers: shared.active.load(atomic::Ordering::Acquire),
+            queued: shared.scheduler.queued(),
+            failed: shared.failed.load(atomic::Ordering::Relaxed),
+            ..PoolMetrics::default()
+        };
+        if let Some(blocking) = self.blocking.get() {
+            metrics.blocking_workers = blocking.threads();
+            metrics.active_blocking_workers = blocking.threads().saturating_sub(blocking.idle());
+            metrics.blocking_queued = blocking.queued();
+        }
+        metrics
+    }
+
+    /// Blocks until every task committed so far has run, without shutting the pool down.
+    /// That includes tasks from `commit_after` that aren't due yet and retried tasks waiting
+    /// for their next attempt, but not `commit_periodic`, which never ends.
+    /// Never returns when called from a task of this pool, as that task is in flight itself.
+    pub fn wait_idle(&self) {
+        self.shared.wait_idle(None);
+    }
+
+    /// Like `wait_idle`, but gives up after `timeout`. Returns whether the pool became idle.
+    pub fn wait_idle_timeout(&self, timeout: Duration) -> bool {
+        self.shared.wait_idle(Some(Instant::now() + timeout))
     }
 
     pub fn terminate(self) {}
 }
 
+impl Executor for ThreadPool {
+    type Handle<T: Send + 'static> = TaskHandle<T>;
+
+    fn execute<F>(&self, f: F)
+    where F: FnOnce() + Send + 'static {
+        self.commit(f);
+    }
+
+    fn submit<F, T>(&self, f: F) -> TaskHandle<T>
+    where F: FnOnce() -> T + Send + 'static, T: Send + 'static {
+        self.commit(f)
+    }
+
+    fn shutdown(self) {
+        self.terminate();
+    }
+}
+
 impl Drop for ThreadPool {
     fn drop(&mut self) {
-        self.scheduler.terminate();
+        self.shared.terminated.store(true, atomic::Ordering::Release);
+        if let Some(delayed) = self.delayed.get() {
+            delayed.terminate();
+        }
+        self.shared.scheduler.terminate();
+
+        // replacement workers may still be spawned while we're joining
+        loop {
+            let worker = self.shared.workers.lock().expect("mutex poisoned.").pop();
+            match worker {
+                Some(mut worker) => worker.stop(),
+                None => break,
+            }
+        }
 
-        for worker in &mut self.workers {
-            worker.stop();
+        if let Some(blocking) = self.blocking.get() {
+            blocking.terminate();
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,51 @@
+#![allow(unused)]
+
+use std::time::Duration;
+
+use crate::{error::Error, sheduler::{NextTask, Scheduler}, AsTask};
+
+/// Decides what happens to a task `ThreadPool::commit` can't queue,
+/// because the queue is at capacity or the pool is shutting down.
+pub trait RejectionHandler: Send + Sync {
+    /// An `Err` is returned to callers of `ThreadPool::try_commit`.
+    fn rejected(&self, task: Box<dyn AsTask>, scheduler: &dyn Scheduler) -> Result<(), Error>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum RejectionPolicy {
+    /// The task's handle resolves with `Error::Rejected`.
+    #[default]
+    Abort,
+    /// The task runs on the committing thread.
+    CallerRuns,
+    /// The oldest queued task is cancelled to make room.
+    DiscardOldest,
+    /// The task is cancelled.
+    Discard,
+}
+
+impl RejectionHandler for RejectionPolicy {
+    fn rejected(&self, task: Box<dyn AsTask>, scheduler: &dyn Scheduler) -> Result<(), Error> {
+        match self {
+            RejectionPolicy::Abort => {
+                task.abort(Error::Rejected);
+                Err(Error::Rejected)
+            },
+            RejectionPolicy::CallerRuns => {
+                task.run();
+                Ok(())
+            },
+            RejectionPolicy::DiscardOldest => {
+                if let NextTask::Task(oldest) = scheduler.next_task_timeout(Duration::ZERO) {
+                    oldest.cancel();
+                }
+                scheduler.schedule(task);
+                Ok(())
+            },
+            RejectionPolicy::Discard => {
+                task.cancel();
+                Ok(())
+            },
+        }
+    }
+}
@@ -0,0 +1,173 @@
+#![allow(unused)]
+
+use std::{any::{Any, TypeId}, sync::{atomic, Arc, Condvar, Mutex}, time::Instant};
+
+use crate::{error::Error, sheduler::{self, Scheduler}, AsTask, TaskMeta};
+
+use super::{worker::Worker, RejectionHandler};
+
+/// State shared between a pool and its worker threads.
+pub(crate) struct Shared {
+    pub(crate) id: usize,
+    pub(crate) size: usize,
+    pub(crate) scheduler: Arc<dyn Scheduler>,
+    pub(crate) workers: Mutex<Vec<Worker>>,
+    pub(crate) replace_stuck_workers: bool,
+    pub(crate) cores: Vec<usize>, // worker `i` is pinned to `cores[i % len]`, if any
+    pub(crate) worker_state: Option<WorkerState>,
+    pub(crate) queue_capacity: Option<usize>,
+    pub(crate) rejection_handler: Box<dyn RejectionHandler>,
+    pub(crate) terminated: atomic::AtomicBool,
+    next_worker_id: atomic::AtomicUsize,
+    // workers currently running a task
+    pub(crate) active: atomic::AtomicUsize,
+    // workers currently blocked in `TaskHandle::wait` on a task of this pool
+    pub(crate) blocked: atomic::AtomicUsize,
+    // tasks that ended as `TaskState::Failed`
+    pub(crate) failed: atomic::AtomicUsize,
+    // kept apart from `Shared`, as queued tasks hold on to it
+    in_flight: Arc<InFlightTasks>,
+}
+
+/// Tasks committed and not yet run or dropped, see `Tracked`.
+pub(crate) struct InFlightTasks {
+    count: Mutex<usize>,
+    idle: Condvar,
+}
+
+impl InFlightTasks {
+    /// Counts `task` as in flight until it has run or was dropped.
+    pub(crate) fn track(self: &Arc<Self>, task: Box<dyn AsTask>) -> Box<dyn AsTask> {
+        *self.count.lock().expect("mutex poisoned.") += 1;
+        Box::new(Tracked {
+            task: Some(task),
+            in_flight: self.clone(),
+        })
+    }
+}
+
+impl Shared {
+    pub(crate) fn new(id: usize, size: usize, scheduler: Arc<dyn Scheduler>) -> Self {
+        Self {
+            id,
+            size,
+            scheduler,
+            workers: Mutex::new(Vec::with_capacity(size)),
+            replace_stuck_workers: false,
+            cores: Vec::new(),
+            worker_state: None,
+            queue_capacity: None,
+            rejection_handler: Box::new(super::RejectionPolicy::default()),
+            terminated: atomic::AtomicBool::new(false),
+            next_worker_id: atomic::AtomicUsize::new(0),
+            active: atomic::AtomicUsize::new(0),
+            blocked: atomic::AtomicUsize::new(0),
+            failed: atomic::AtomicUsize::new(0),
+            in_flight: Arc::new(InFlightTasks {
+                count: Mutex::new(0),
+                idle: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Counts `task` as in flight until it has run or was dropped.
+    pub(crate) fn track(&self, task: Box<dyn AsTask>) -> Box<dyn AsTask> {
+        self.in_flight.track(task)
+    }
+
+    /// For tasks that put themselves back in flight, see `RetryTask`.
+    pub(crate) fn in_flight(&self) -> Arc<InFlightTasks> {
+        self.in_flight.clone()
+    }
+
+    /// Hands the task to the scheduler, or to the rejection handler if it can't take it.
+    pub(crate) fn submit(&self, task: Box<dyn AsTask>) -> Result<(), Error> {
+        let full = self.queue_capacity.is_some_and(|capacity| self.scheduler.queued() >= capacity);
+        if full || self.terminated.load(atomic::Ordering::Acquire) {
+            return self.rejection_handler.rejected(task, &*self.scheduler);
+        }
+
+        self.scheduler.schedule(task);
+        Ok(())
+    }
+
+    /// Waits until no task is in flight, or until `deadline`. Returns whether the pool is idle.
+    pub(crate) fn wait_idle(&self, deadline: Option<Instant>) -> bool {
+        let in_flight = self.in_flight.count.lock().expect("mutex poisoned.");
+        sheduler::wait_until(&self.in_flight.idle, in_flight, deadline, |in_flight| (*in_flight == 0).then_some(()))
+            .is_some()
+    }
+
+    pub(crate) fn spawn_worker(self: &Arc<Self>) {
+        let id = self.next_worker_id.fetch_add(1, atomic::Ordering::Relaxed);
+        let worker = Worker::new(id, self.clone());
+        self.workers.lock().expect("mutex poisoned.").push(worker);
+    }
+}
+
+type StateInit = Box<dyn Fn(usize) -> Box<dyn Any> + Send + Sync>;
+type StateTeardown = Box<dyn Fn(usize, Box<dyn Any>) + Send + Sync>;
+
+/// The type-erased `ThreadPoolBuilder::worker_state`, created on each worker's own thread.
+pub(crate) struct WorkerState {
+    pub(crate) type_id: TypeId,
+    pub(crate) init: StateInit,
+    pub(crate) teardown: Option<StateTeardown>,
+}
+
+/// A committed task, in flight until it has run or was dropped.
+struct Tracked {
+    task: Option<Box<dyn AsTask>>,
+    in_flight: Arc<InFlightTasks>,
+}
+
+impl AsTask for Tracked {
+    fn run(mut self: Box<Self>) {
+        if let Some(task) = self.task.take() {
+            task.run();
+        }
+    }
+
+    fn abort(&self, reason: Error) {
+        if let Some(task) = &self.task {
+            task.abort(reason);
+        }
+    }
+
+    fn meta(&self) -> &TaskMeta {
+        self.task.as_ref().expect("task already run.").meta()
+    }
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        let mut in_flight = self.in_flight.count.lock().expect("mutex poisoned.");
+        *in_flight -= 1;
+        if *in_flight == 0 {
+            self.in_flight.idle.notify_all();
+        }
+    }
+}
+
+/// Counts the calling worker as blocked for as long as it's alive.
+pub(crate) struct Blocked(Arc<Shared>);
+
+impl Blocked {
+    /// Called before a worker blocks on a task of its own pool.
+    /// Fails with `Error::WouldDeadlock` if the task is still pending and every other
+    /// worker is blocked as well, since nobody would ever pick it up.
+    pub(crate) fn enter(shared: Arc<Shared>, task_pending: bool) -> Result<Self, Error> {
+        let blocked = shared.blocked.fetch_add(1, atomic::Ordering::AcqRel) + 1;
+        if task_pending && blocked >= shared.size {
+            shared.blocked.fetch_sub(1, atomic::Ordering::AcqRel);
+            return Err(Error::WouldDeadlock);
+        }
+        Ok(Self(shared))
+    }
+}
+
+impl Drop for Blocked {
+    fn drop(&mut self) {
+        self.0.blocked.fetch_sub(1, atomic::Ordering::AcqRel);
+    }
+}
@@ -1,19 +1,139 @@
 #![allow(unused)]
 
-use std::{sync::Arc, thread};
+use std::{any::Any, cell::RefCell, marker::PhantomData, sync::{atomic, Arc}, thread};
 
-use crate::{sheduler::Scheduler, TaskState};
+use crate::{sheduler::{NextTask, Scheduler}, task::Task, AsTask, Error, TaskMeta, TaskState};
 
-pub(super) struct Worker {
+use super::{affinity, shared::Shared};
+
+thread_local! {
+    // the worker running on this thread, None for non-worker threads
+    static CURRENT_WORKER: RefCell<Option<WorkerRef>> = const { RefCell::new(None) };
+    // the worker's state, see `ThreadPoolBuilder::worker_state`
+    static WORKER_STATE: RefCell<Option<Box<dyn Any>>> = const { RefCell::new(None) };
+}
+
+/// Returns the shared state of the pool the calling thread is a worker of.
+pub(crate) fn current_pool() -> Option<Arc<Shared>> {
+    CURRENT_WORKER.with(|worker| worker.borrow().as_ref().map(|worker| worker.shared.clone()))
+}
+
+/// Whether the calling thread has a worker state of type `S` that isn't in use.
+fn has_state<S: 'static>() -> bool {
+    WORKER_STATE.with(|state| state.borrow().as_ref().is_some_and(|state| state.is::<S>()))
+}
+
+/// Runs `f` with the state of the worker running on the calling thread.
+/// Panics if there is none of type `S`, see `StateTask` for how that's avoided.
+pub(crate) fn with_state<S: 'static, R>(f: impl FnOnce(&mut S) -> R) -> R {
+    // taken out for the call, so a nested task on the same worker can't alias it
+    let mut state = WORKER_STATE.with(|state| state.borrow_mut().take())
+        .expect("task needs the worker state of its pool.");
+    let result = f(state.downcast_mut().expect("worker state of another type."));
+    WORKER_STATE.with(|slot| *slot.borrow_mut() = Some(state));
+    result
+}
+
+/// A task of `ThreadPool::commit_with_state`. It resolves with `Error::NoWorkerState`
+/// instead of running where the state isn't available, e.g. on the committing thread
+/// under `RejectionPolicy::CallerRuns`, or in `ThreadPool::join` within another state task.
+pub(crate) struct StateTask<T, S> {
+    task: Box<Task<T>>,
+    state: PhantomData<fn(&mut S)>,
+}
+
+impl<T, S> StateTask<T, S> {
+    pub(crate) fn new(task: Task<T>) -> Self {
+        Self {
+            task: Box::new(task),
+            state: PhantomData,
+        }
+    }
+}
+
+impl<T: Send + 'static, S: 'static> AsTask for StateTask<T, S> {
+    fn run(self: Box<Self>) {
+        if has_state::<S>() {
+            self.task.run();
+        } else {
+            self.task.abort(Error::NoWorkerState);
+        }
+    }
+
+    fn abort(&self, reason: Error) {
+        self.task.abort(reason);
+    }
+
+    fn meta(&self) -> &TaskMeta {
+        self.task.meta()
+    }
+}
+
+/// Returns the worker running on the calling thread.
+pub(crate) fn current_worker() -> Option<WorkerRef> {
+    CURRENT_WORKER.with(|worker| worker.borrow().clone())
+}
+
+#[derive(Clone)]
+pub(crate) struct WorkerRef {
+    shared: Arc<Shared>,
+    retire: Arc<atomic::AtomicBool>,
+}
+
+impl WorkerRef {
+    /// Called when the worker is stuck on a task. If the pool is configured to do so,
+    /// a new worker takes its place and this one exits once its current task returns.
+    pub(crate) fn replace(&self) {
+        if self.shared.replace_stuck_workers && !self.retire.swap(true, atomic::Ordering::AcqRel) {
+            self.shared.spawn_worker();
+        }
+    }
+}
+
+pub(crate) struct Worker {
     id: usize,
     thread: Option<thread::JoinHandle<()>>,
 }
 
 impl Worker {
-    pub fn new(id: usize, scheduler: Arc<dyn Scheduler>) -> Self {
+    pub fn new(id: usize, shared: Arc<Shared>) -> Self {
         let thread = Some(thread::spawn(move || {
-            while let Some(mut task) = scheduler.next_task() {
-                task.run();
+            if !shared.cores.is_empty() {
+                affinity::pin_current_thread(shared.cores[id % shared.cores.len()]);
+            }
+
+            let retire = Arc::new(atomic::AtomicBool::new(false));
+            let scheduler = shared.scheduler.clone();
+            CURRENT_WORKER.with(|worker| *worker.borrow_mut() = Some(WorkerRef {
+                shared: shared.clone(),
+                retire: retire.clone(),
+            }));
+            if let Some(state) = &shared.worker_state {
+                WORKER_STATE.with(|slot| *slot.borrow_mut() = Some((state.init)(id)));
+            }
+
+            loop {
+                match scheduler.next_task() {
+                    NextTask::Task(task) => {
+                        shared.active.fetch_add(1, atomic::Ordering::AcqRel);
+                        task.run();
+                        shared.active.fetch_sub(1, atomic::Ordering::AcqRel);
+                        if retire.load(atomic::Ordering::Acquire) {
+                            break;
+                        }
+                    },
+                    NextTask::Idle => continue,
+                    NextTask::Shutdown => break,
+                }
+            }
+
+            if let Some(teardown) = shared.worker_state.as_ref().and_then(|state| state.teardown.as_ref())
+                && let Some(state) = WORKER_STATE.with(|slot| slot.borrow_mut().take()) {
+                teardown(id, state);
+            }
+            // a replaced worker leaves on its own, the pool only joins the others
+            if retire.load(atomic::Ordering::Acquire) {
+                shared.workers.lock().expect("mutex poisoned.").retain(|worker| worker.id != id);
             }
 
             println!("Worker {} exiting.", id);
@@ -27,7 +147,8 @@ impl Worker {
 
     pub fn stop(&mut self) {
         if let Some(thread) = self.thread.take() {
-            thread.join().unwrap();
+            // a worker killed by a panicking task has nothing left to clean up
+            thread.join().ok();
         }
     }
-}
\ No newline at end of file
+}
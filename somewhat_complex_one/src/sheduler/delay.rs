@@ -0,0 +1,161 @@
+#![allow(unused)]
+
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    sync::{Arc, Condvar, Mutex},
+    thread,
+    time::Instant,
+};
+
+use crate::AsTask;
+
+enum Job {
+    Task(Box<dyn AsTask>),
+    // runs on the timer thread itself, so it must be short
+    Call(Box<dyn FnOnce() + Send>),
+}
+
+struct Entry {
+    due: Instant,
+    seq: u64, // keeps jobs due at the same instant in commit order
+    job: Job,
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.due == other.due && self.seq == other.seq
+    }
+}
+
+impl Eq for Entry {}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Entry {
+    // reversed, so the max-heap pops the earliest entry first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.due.cmp(&self.due).then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+struct Timeline {
+    entries: BinaryHeap<Entry>,
+    next_seq: u64,
+    terminated: bool,
+}
+
+/// Holds tasks until they are due and then hands them to `submit`, i.e. the pool's queue.
+/// Waiting happens on a dedicated timer thread, so no worker sleeps on a delayed task.
+pub(crate) struct DelayQueue {
+    timeline: Arc<(Mutex<Timeline>, Condvar)>,
+    timer: Mutex<Option<thread::JoinHandle<()>>>,
+}
+
+impl DelayQueue {
+    pub(crate) fn new<F>(submit: F) -> Self
+    where F: Fn(Box<dyn AsTask>) + Send + 'static {
+        let timeline = Arc::new((Mutex::new(Timeline {
+            entries: BinaryHeap::new(),
+            next_seq: 0,
+            terminated: false,
+        }), Condvar::new()));
+
+        let timeline_clone = timeline.clone();
+        let timer = thread::spawn(move || {
+            let (timeline, condvar) = &*timeline_clone;
+            let mut timeline = timeline.lock().expect("mutex poisoned.");
+
+            while !timeline.terminated {
+                let now = Instant::now();
+                match timeline.entries.peek().map(|entry| entry.due) {
+                    Some(due) if due <= now => {
+                        match timeline.entries.pop().unwrap().job {
+                            Job::Task(task) => submit(task),
+                            Job::Call(call) => {
+                                drop(timeline);
+                                call();
+                                timeline = timeline_clone.0.lock().expect("mutex poisoned.");
+                            },
+                        }
+                    },
+                    Some(due) => {
+                        timeline = condvar.wait_timeout(timeline, due - now).expect("mutex poisoned.").0;
+                    },
+                    None => {
+                        timeline = condvar.wait(timeline).expect("mutex poisoned.");
+                    },
+                }
+            }
+        });
+
+        Self {
+            timeline,
+            timer: Mutex::new(Some(timer)),
+        }
+    }
+
+    /// Submits `task` once `due` has passed.
+    /// Tasks delayed after termination are cancelled right away.
+    pub(crate) fn schedule_at(&self, due: Instant, task: Box<dyn AsTask>) {
+        if let Err(Job::Task(task)) = self.push(due, Job::Task(task)) {
+            task.cancel();
+        }
+    }
+
+    /// Runs `call` on the timer thread once `due` has passed, returns the key to `cancel_call` it.
+    /// Calls pending at termination are dropped without running.
+    pub(crate) fn call_at<F>(&self, due: Instant, call: F) -> Option<u64>
+    where F: FnOnce() + Send + 'static {
+        self.push(due, Job::Call(Box::new(call))).ok()
+    }
+
+    /// Drops a call that hasn't run yet, along with whatever it captured.
+    pub(crate) fn cancel_call(&self, key: u64) {
+        // dropped once the lock is released
+        let cancelled: BinaryHeap<Entry> = {
+            let mut timeline = self.timeline.0.lock().expect("mutex poisoned.");
+            let (cancelled, entries) = std::mem::take(&mut timeline.entries)
+                .into_iter()
+                .partition(|entry| entry.seq == key);
+            timeline.entries = entries;
+            cancelled
+        };
+    }
+
+    fn push(&self, due: Instant, job: Job) -> Result<u64, Job> {
+        let (timeline, condvar) = &*self.timeline;
+        let mut timeline = timeline.lock().expect("mutex poisoned.");
+        if timeline.terminated {
+            return Err(job);
+        }
+
+        let seq = timeline.next_seq;
+        timeline.next_seq += 1;
+        timeline.entries.push(Entry { due, seq, job });
+        condvar.notify_one();
+        Ok(seq)
+    }
+
+    /// Stops the timer thread and cancels every task that isn't due yet.
+    pub(crate) fn terminate(&self) {
+        let (timeline, condvar) = &*self.timeline;
+        timeline.lock().expect("mutex poisoned.").terminated = true;
+        condvar.notify_all();
+
+        if let Some(timer) = self.timer.lock().expect("mutex poisoned.").take() {
+            timer.join().unwrap();
+        }
+
+        let mut timeline = timeline.lock().expect("mutex poisoned.");
+        for entry in timeline.entries.drain() {
+            if let Job::Task(task) = entry.job {
+                task.cancel();
+            }
+        }
+    }
+}
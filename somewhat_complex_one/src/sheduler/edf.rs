@@ -0,0 +1,147 @@
+#![allow(unused)]
+
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    sync::{atomic, Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{error::{Error, ErrorContext}, AsTask, TaskState};
+
+use super::{NextTask, Scheduler};
+
+struct Entry {
+    deadline: Option<Instant>,
+    seq: u64,
+    task: Box<dyn AsTask>,
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.deadline == other.deadline && self.seq == other.seq
+    }
+}
+
+impl Eq for Entry {}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Entry {
+    // reversed for the max-heap: earliest deadline first, tasks without one last,
+    // ties in commit order
+    fn cmp(&self, other: &Self) -> Ordering {
+        let by_deadline = match (self.deadline, other.deadline) {
+            (Some(a), Some(b)) => b.cmp(&a),
+            (Some(_), None) => Ordering::Greater,
+            (None, Some(_)) => Ordering::Less,
+            (None, None) => Ordering::Equal,
+        };
+        by_deadline.then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+struct Queue {
+    entries: BinaryHeap<Entry>,
+    next_seq: u64,
+}
+
+/// Hands out the task with the earliest deadline first, see `ThreadPool::commit_with_deadline`.
+pub struct EdfScheduler {
+    queue: Mutex<Queue>,
+    condvar: Condvar,
+    drop_missed: bool,
+    terminate_flag: atomic::AtomicBool,
+}
+
+impl EdfScheduler {
+    pub fn new() -> Self {
+        Self {
+            queue: Mutex::new(Queue {
+                entries: BinaryHeap::new(),
+                next_seq: 0,
+            }),
+            condvar: Condvar::new(),
+            drop_missed: false,
+            terminate_flag: atomic::AtomicBool::new(false),
+        }
+    }
+
+    /// Tasks whose deadline has passed before they start are aborted with
+    /// `Error::DeadlineMissed` instead of being run.
+    pub fn drop_missed(self, drop_missed: bool) -> Self {
+        Self {
+            drop_missed,
+            ..self
+        }
+    }
+
+    fn pop(&self, queue: &mut Queue) -> Option<Box<dyn AsTask>> {
+        while let Some(entry) = queue.entries.pop() {
+            if self.drop_missed && entry.deadline.is_some_and(|deadline| deadline < Instant::now()) {
+                let context = ErrorContext::new(entry.task.meta(), TaskState::Pending);
+                entry.task.abort(Error::DeadlineMissed(context));
+                continue;
+            }
+            return Some(entry.task);
+        }
+        None
+    }
+
+    fn wait_for_task(&self, deadline: Option<Instant>) -> NextTask {
+        let queue = self.queue.lock().expect("mutex poisoned.");
+        super::wait_until(&self.condvar, queue, deadline, |queue| {
+            if self.terminate_flag.load(atomic::Ordering::Acquire) {
+                return Some(NextTask::Shutdown);
+            }
+            self.pop(queue).map(NextTask::Task)
+        })
+        .unwrap_or(NextTask::Idle)
+    }
+}
+
+impl Default for EdfScheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scheduler for EdfScheduler {
+    fn schedule(&self, task: Box<dyn AsTask>) {
+        let mut queue = self.queue.lock().expect("mutex poisoned.");
+        let seq = queue.next_seq;
+        queue.next_seq += 1;
+        queue.entries.push(Entry {
+            deadline: task.meta().deadline(),
+            seq,
+            task,
+        });
+        self.condvar.notify_one();
+    }
+
+    fn next_task(&self) -> NextTask {
+        self.wait_for_task(None)
+    }
+
+    fn next_task_timeout(&self, timeout: Duration) -> NextTask {
+        self.wait_for_task(Some(Instant::now() + timeout))
+    }
+
+    fn queued(&self) -> usize {
+        self.queue.lock().expect("mutex poisoned.").entries.len()
+    }
+
+    fn terminate(&self) {
+        let remain_tasks: Vec<_> = {
+            let mut queue = self.queue.lock().expect("mutex poisoned.");
+            self.terminate_flag.store(true, atomic::Ordering::Release);
+            self.condvar.notify_all();
+            queue.entries.drain().map(|entry| entry.task).collect()
+        };
+        super::cancel_remaining(remain_tasks);
+    }
+}
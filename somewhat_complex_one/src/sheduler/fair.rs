@@ -0,0 +1,218 @@
+#![allow(unused)]
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{atomic, Arc, Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{error::Error, AsTask, TaskMeta};
+
+use super::{NextTask, Scheduler};
+
+// tasks committed without a tenant share this one
+const DEFAULT_TENANT: &str = "";
+
+struct TenantQueue {
+    tasks: VecDeque<Box<dyn AsTask>>,
+    running: usize,
+    weight: usize,
+    max_concurrency: usize,
+    credit: usize, // picks left in the current round
+}
+
+struct Tenants {
+    queues: HashMap<Arc<str>, TenantQueue>,
+    order: Vec<Arc<str>>,
+    cursor: usize,
+    queued: usize,
+}
+
+struct Config {
+    weights: HashMap<String, usize>,
+    max_concurrency: HashMap<String, usize>,
+}
+
+struct Inner {
+    tenants: Mutex<Tenants>,
+    condvar: Condvar,
+    config: Config,
+}
+
+/// Keeps a queue per tenant and serves them in weighted round-robin,
+/// a tenant with weight `n` gets up to `n` tasks in a row before the next one's turn.
+/// Tenants are set with `ThreadPool::commit_for`.
+pub struct FairScheduler {
+    inner: Arc<Inner>,
+    terminate_flag: atomic::AtomicBool,
+}
+
+/// Gives the tenant's slot back once the task has run or was dropped.
+struct FairTask {
+    task: Option<Box<dyn AsTask>>,
+    tenant: Arc<str>,
+    inner: Arc<Inner>,
+}
+
+impl FairScheduler {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                tenants: Mutex::new(Tenants {
+                    queues: HashMap::new(),
+                    order: Vec::new(),
+                    cursor: 0,
+                    queued: 0,
+                }),
+                condvar: Condvar::new(),
+                config: Config {
+                    weights: HashMap::new(),
+                    max_concurrency: HashMap::new(),
+                },
+            }),
+            terminate_flag: atomic::AtomicBool::new(false),
+        }
+    }
+
+    /// Share of turns for `tenant`, 1 by default.
+    pub fn weight(mut self, tenant: &str, weight: usize) -> Self {
+        Arc::get_mut(&mut self.inner).unwrap().config.weights.insert(tenant.to_string(), weight.max(1));
+        self
+    }
+
+    /// Most tasks of `tenant` that may run at the same time, unlimited by default.
+    pub fn max_concurrency(mut self, tenant: &str, max: usize) -> Self {
+        Arc::get_mut(&mut self.inner).unwrap().config.max_concurrency.insert(tenant.to_string(), max.max(1));
+        self
+    }
+}
+
+impl FairScheduler {
+    fn wait_for_task(&self, deadline: Option<Instant>) -> NextTask {
+        let tenants = self.inner.tenants.lock().expect("mutex poisoned.");
+        super::wait_until(&self.inner.condvar, tenants, deadline, |tenants| {
+            if self.terminate_flag.load(atomic::Ordering::Acquire) {
+                return Some(NextTask::Shutdown);
+            }
+            tenants.pick().map(|picked| NextTask::Task(self.inner.wrap(picked)))
+        })
+        .unwrap_or(NextTask::Idle)
+    }
+}
+
+impl Default for FairScheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tenants {
+    fn push(&mut self, tenant: Arc<str>, task: Box<dyn AsTask>, config: &Config) {
+        if !self.queues.contains_key(&tenant) {
+            let weight = config.weights.get(&*tenant).copied().unwrap_or(1);
+            self.queues.insert(tenant.clone(), TenantQueue {
+                tasks: VecDeque::new(),
+                running: 0,
+                weight,
+                max_concurrency: config.max_concurrency.get(&*tenant).copied().unwrap_or(usize::MAX),
+                credit: weight,
+            });
+            self.order.push(tenant.clone());
+        }
+        self.queues.get_mut(&tenant).unwrap().tasks.push_back(task);
+        self.queued += 1;
+    }
+
+    // one pass over the tenants, starting with the one whose turn it is
+    fn pick(&mut self) -> Option<(Arc<str>, Box<dyn AsTask>)> {
+        for _ in 0..self.order.len() {
+            let tenant = self.order[self.cursor].clone();
+            let queue = self.queues.get_mut(&tenant).unwrap();
+            if !queue.tasks.is_empty() && queue.running < queue.max_concurrency {
+                let task = queue.tasks.pop_front().unwrap();
+                queue.running += 1;
+                queue.credit -= 1;
+                if queue.credit == 0 {
+                    queue.credit = queue.weight;
+                    self.cursor = (self.cursor + 1) % self.order.len();
+                }
+                self.queued -= 1;
+                return Some((tenant, task));
+            }
+            queue.credit = queue.weight;
+            self.cursor = (self.cursor + 1) % self.order.len();
+        }
+        None
+    }
+}
+
+impl Inner {
+    fn wrap(self: &Arc<Self>, (tenant, task): (Arc<str>, Box<dyn AsTask>)) -> Box<dyn AsTask> {
+        Box::new(FairTask {
+            task: Some(task),
+            tenant,
+            inner: self.clone(),
+        })
+    }
+}
+
+impl Scheduler for FairScheduler {
+    fn schedule(&self, task: Box<dyn AsTask>) {
+        let tenant: Arc<str> = task.meta().tenant().unwrap_or(DEFAULT_TENANT).into();
+        let mut tenants = self.inner.tenants.lock().expect("mutex poisoned.");
+        tenants.push(tenant, task, &self.inner.config);
+        self.inner.condvar.notify_one();
+    }
+
+    fn next_task(&self) -> NextTask {
+        self.wait_for_task(None)
+    }
+
+    fn next_task_timeout(&self, timeout: Duration) -> NextTask {
+        self.wait_for_task(Some(Instant::now() + timeout))
+    }
+
+    fn queued(&self) -> usize {
+        self.inner.tenants.lock().expect("mutex poisoned.").queued
+    }
+
+    fn terminate(&self) {
+        let remain_tasks: Vec<_> = {
+            let mut tenants = self.inner.tenants.lock().expect("mutex poisoned.");
+            self.terminate_flag.store(true, atomic::Ordering::Release);
+            self.inner.condvar.notify_all();
+            tenants.queued = 0;
+            tenants.queues.values_mut().flat_map(|queue| queue.tasks.drain(..)).collect()
+        };
+        super::cancel_remaining(remain_tasks);
+    }
+}
+
+impl AsTask for FairTask {
+    fn run(mut self: Box<Self>) {
+        if let Some(task) = self.task.take() {
+            task.run();
+        }
+    }
+
+    fn abort(&self, reason: Error) {
+        if let Some(task) = &self.task {
+            task.abort(reason);
+        }
+    }
+
+    fn meta(&self) -> &TaskMeta {
+        self.task.as_ref().expect("task already run.").meta()
+    }
+}
+
+impl Drop for FairTask {
+    fn drop(&mut self) {
+        let mut tenants = self.inner.tenants.lock().expect("mutex poisoned.");
+        if let Some(queue) = tenants.queues.get_mut(&self.tenant) {
+            queue.running -= 1;
+        }
+        // the tenant may have been held back by its concurrency limit
+        self.inner.condvar.notify_all();
+    }
+}
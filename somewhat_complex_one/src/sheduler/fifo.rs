@@ -1,13 +1,15 @@
 #![allow(unused)]
 
-use std::{collections::VecDeque, sync::{atomic, Arc, Condvar, Mutex}};
+use std::{collections::VecDeque, sync::{atomic, Arc, Condvar, Mutex}, time::{Duration, Instant}};
 
 use crate::{task::Task, AsTask};
 
-use super::Scheduler;
+use super::{NextTask, Scheduler};
+
+type TaskQueue = Arc<(Mutex<VecDeque<Box<dyn AsTask>>>, Condvar)>;
 
 pub struct FifoScheduler {
-    task_queue: Arc<(Mutex<VecDeque<Box<dyn AsTask>>>, Condvar)>,
+    task_queue: TaskQueue,
     terminate_flag: atomic::AtomicBool,
 }
 
@@ -18,6 +20,24 @@ impl FifoScheduler {
             terminate_flag: atomic::AtomicBool::new(false),
         }
     }
+
+    fn wait_for_task(&self, deadline: Option<Instant>) -> NextTask {
+        let (queue, condvar) = &*self.task_queue;
+        let queue = queue.lock().expect("mutex poisoned.");
+        super::wait_until(condvar, queue, deadline, |queue| {
+            if self.terminate_flag.load(atomic::Ordering::Acquire) {
+                return Some(NextTask::Shutdown);
+            }
+            queue.pop_front().map(NextTask::Task)
+        })
+        .unwrap_or(NextTask::Idle)
+    }
+}
+
+impl Default for FifoScheduler {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Scheduler for FifoScheduler {
@@ -36,31 +56,27 @@ impl Scheduler for FifoScheduler {
         };
     }
 
-    fn next_task(&self) -> Option<Box<dyn AsTask>> {
-        let (queue, condvar) = &*self.task_queue;
-        let mut queue = queue.lock().expect("mutex poisoned.");
-        
-        loop {
-            if self.terminate_flag.load(atomic::Ordering::Acquire) {
-                break None;
-            }
+    fn next_task(&self) -> NextTask {
+        self.wait_for_task(None)
+    }
 
-            match queue.pop_front() {
-                Some(task) => return Some(task),
-                None =>  {
-                    queue = condvar.wait(queue).expect("mutex poisoned.");
-                },
-            }   
-        }
+    fn next_task_timeout(&self, timeout: Duration) -> NextTask {
+        self.wait_for_task(Some(Instant::now() + timeout))
     }
     
+    fn queued(&self) -> usize {
+        self.task_queue.0.lock().expect("mutex poisoned.").len()
+    }
+
     fn terminate(&self) {
-        self.terminate_flag.store(true, atomic::Ordering::Release);
-        let condvar = &self.task_queue.1;
-        condvar.notify_all();
-        let remain_tasks = &*self.task_queue.0.lock().expect("mutex poisoned.");
-        for task in remain_tasks {
-            task.cancel();
-        }
+        let (queue, condvar) = &*self.task_queue;
+        let remain_tasks: Vec<_> = {
+            let mut queue = queue.lock().expect("mutex poisoned.");
+            // set under the lock, so no worker can miss the wakeup
+            self.terminate_flag.store(true, atomic::Ordering::Release);
+            condvar.notify_all();
+            queue.drain(..).collect()
+        };
+        super::cancel_remaining(remain_tasks);
     }
-}
\ No newline at end of file
+}
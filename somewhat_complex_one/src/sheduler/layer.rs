@@ -0,0 +1,311 @@
+#![allow(unused)]
+
+use std::{
+    cell::RefCell,
+    sync::{atomic, Arc, Condvar, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{error::Error, AsTask, TaskMeta};
+
+use super::{NextTask, Scheduler};
+
+thread_local! {
+    // slots held by the tasks running on this thread, see `lend_slots`
+    static HELD_SLOTS: RefCell<Vec<Arc<Slots>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Runs `f` with the concurrency slots of the calling worker's tasks given back, so a
+/// worker helping out in `ThreadPool::join` can pick up more tasks under the same limit.
+/// The slots are taken again before returning, which may wait for other tasks to finish.
+pub(crate) fn lend_slots<R>(f: impl FnOnce() -> R) -> R {
+    let held = HELD_SLOTS.with(|held| held.take());
+    for slots in &held {
+        slots.release();
+    }
+    let result = f();
+    for slots in &held {
+        slots.reacquire();
+    }
+    HELD_SLOTS.with(|slot| *slot.borrow_mut() = held);
+    result
+}
+
+/// Wraps a scheduler to add behavior on top of it, see `ThreadPoolBuilder::layer`.
+pub trait SchedulerLayer: Send + Sync {
+    fn layer(&self, inner: Arc<dyn Scheduler>) -> Arc<dyn Scheduler>;
+}
+
+/// Limits how many tasks start per second with a token bucket,
+/// allowing bursts of up to `burst` tasks after an idle period.
+pub struct RateLimitLayer {
+    per_second: f64,
+    burst: usize,
+}
+
+impl RateLimitLayer {
+    /// Panics unless `per_second` is positive and finite.
+    pub fn new(per_second: f64, burst: usize) -> Self {
+        assert!(per_second > 0.0 && per_second.is_finite(), "rate limit must be positive and finite.");
+        Self {
+            per_second,
+            burst: burst.max(1),
+        }
+    }
+}
+
+impl SchedulerLayer for RateLimitLayer {
+    fn layer(&self, inner: Arc<dyn Scheduler>) -> Arc<dyn Scheduler> {
+        Arc::new(RateLimited {
+            inner,
+            per_second: self.per_second,
+            burst: self.burst as f64,
+            bucket: Mutex::new(Bucket {
+                tokens: self.burst as f64,
+                refilled_at: Instant::now(),
+            }),
+        })
+    }
+}
+
+struct Bucket {
+    tokens: f64, // negative while workers wait for tokens taken ahead of time
+    refilled_at: Instant,
+}
+
+struct RateLimited {
+    inner: Arc<dyn Scheduler>,
+    per_second: f64,
+    burst: f64,
+    bucket: Mutex<Bucket>,
+}
+
+impl Bucket {
+    fn refill(&mut self, per_second: f64, burst: f64) {
+        let now = Instant::now();
+        self.tokens = (self.tokens + (now - self.refilled_at).as_secs_f64() * per_second).min(burst);
+        self.refilled_at = now;
+    }
+}
+
+impl RateLimited {
+    // takes a token, returning how long the caller has to wait for it
+    fn take(&self) -> Duration {
+        let mut bucket = self.bucket.lock().expect("mutex poisoned.");
+        bucket.refill(self.per_second, self.burst);
+        bucket.tokens -= 1.0;
+        Duration::from_secs_f64((-bucket.tokens).max(0.0) / self.per_second)
+    }
+
+    // takes a token if one is free by `deadline`, otherwise waits until then for nothing
+    fn take_by(&self, deadline: Instant) -> bool {
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().expect("mutex poisoned.");
+                bucket.refill(self.per_second, self.burst);
+                if bucket.tokens >= 1.0 {
+                    bucket.tokens -= 1.0;
+                    return true;
+                }
+                Duration::from_secs_f64((1.0 - bucket.tokens) / self.per_second)
+            };
+            let now = Instant::now();
+            if now + wait > deadline {
+                thread::sleep(deadline.saturating_duration_since(now));
+                return false;
+            }
+            thread::sleep(wait);
+        }
+    }
+
+    fn give_back(&self) {
+        let mut bucket = self.bucket.lock().expect("mutex poisoned.");
+        bucket.tokens = (bucket.tokens + 1.0).min(self.burst);
+    }
+}
+
+impl Scheduler for RateLimited {
+    fn schedule(&self, task: Box<dyn AsTask>) {
+        self.inner.schedule(task);
+    }
+
+    // a worker with nothing else to do waits for the token once it has a task
+    fn next_task(&self) -> NextTask {
+        let next = self.inner.next_task();
+        if let NextTask::Task(_) = next {
+            thread::sleep(self.take());
+        }
+        next
+    }
+
+    // the token comes first, so no task is taken that can't start within `timeout`
+    fn next_task_timeout(&self, timeout: Duration) -> NextTask {
+        let deadline = Instant::now() + timeout;
+        if !self.take_by(deadline) {
+            return NextTask::Idle;
+        }
+        let next = self.inner.next_task_timeout(deadline.saturating_duration_since(Instant::now()));
+        if !matches!(next, NextTask::Task(_)) {
+            self.give_back();
+        }
+        next
+    }
+
+    fn terminate(&self) {
+        self.inner.terminate();
+    }
+
+    fn queued(&self) -> usize {
+        self.inner.queued()
+    }
+}
+
+/// Limits how many tasks handed out by the scheduler may be in flight at once.
+pub struct ConcurrencyLimitLayer {
+    max: usize,
+}
+
+impl ConcurrencyLimitLayer {
+    pub fn new(max: usize) -> Self {
+        Self {
+            max: max.max(1),
+        }
+    }
+}
+
+impl SchedulerLayer for ConcurrencyLimitLayer {
+    fn layer(&self, inner: Arc<dyn Scheduler>) -> Arc<dyn Scheduler> {
+        Arc::new(ConcurrencyLimited {
+            inner,
+            slots: Arc::new(Slots {
+                in_flight: Mutex::new(0),
+                condvar: Condvar::new(),
+                max: self.max,
+                terminate_flag: atomic::AtomicBool::new(false),
+            }),
+        })
+    }
+}
+
+struct Slots {
+    in_flight: Mutex<usize>,
+    condvar: Condvar,
+    max: usize,
+    terminate_flag: atomic::AtomicBool,
+}
+
+impl Slots {
+    fn release(&self) {
+        *self.in_flight.lock().expect("mutex poisoned.") -= 1;
+        self.condvar.notify_one();
+    }
+
+    // takes back a lent slot, over the limit once the pool is shutting down
+    fn reacquire(&self) {
+        let in_flight = self.in_flight.lock().expect("mutex poisoned.");
+        super::wait_until(&self.condvar, in_flight, None, |in_flight| {
+            (*in_flight < self.max || self.terminate_flag.load(atomic::Ordering::Acquire)).then(|| *in_flight += 1)
+        });
+    }
+}
+
+struct ConcurrencyLimited {
+    inner: Arc<dyn Scheduler>,
+    slots: Arc<Slots>,
+}
+
+/// Holds a slot until the task has run or was dropped.
+struct InFlight {
+    task: Option<Box<dyn AsTask>>,
+    slots: Arc<Slots>,
+}
+
+impl ConcurrencyLimited {
+    // takes a free slot, waiting for one until `deadline`
+    fn acquire(&self, deadline: Option<Instant>) -> Result<(), NextTask> {
+        let in_flight = self.slots.in_flight.lock().expect("mutex poisoned.");
+        super::wait_until(&self.slots.condvar, in_flight, deadline, |in_flight| {
+            if *in_flight < self.slots.max {
+                *in_flight += 1;
+                return Some(Ok(()));
+            }
+            self.slots.terminate_flag.load(atomic::Ordering::Acquire).then_some(Err(NextTask::Shutdown))
+        })
+        .unwrap_or(Err(NextTask::Idle))
+    }
+
+    // the acquired slot goes with the task, or back if there is none
+    fn track(&self, next: NextTask) -> NextTask {
+        match next {
+            NextTask::Task(task) => NextTask::Task(Box::new(InFlight {
+                task: Some(task),
+                slots: self.slots.clone(),
+            })),
+            other => {
+                self.slots.release();
+                other
+            },
+        }
+    }
+}
+
+impl Scheduler for ConcurrencyLimited {
+    fn schedule(&self, task: Box<dyn AsTask>) {
+        self.inner.schedule(task);
+    }
+
+    fn next_task(&self) -> NextTask {
+        if let Err(next) = self.acquire(None) {
+            return next;
+        }
+        self.track(self.inner.next_task())
+    }
+
+    fn next_task_timeout(&self, timeout: Duration) -> NextTask {
+        let deadline = Instant::now() + timeout;
+        if let Err(next) = self.acquire(Some(deadline)) {
+            return next;
+        }
+        self.track(self.inner.next_task_timeout(deadline.saturating_duration_since(Instant::now())))
+    }
+
+    fn terminate(&self) {
+        {
+            let _in_flight = self.slots.in_flight.lock().expect("mutex poisoned.");
+            self.slots.terminate_flag.store(true, atomic::Ordering::Release);
+        }
+        self.slots.condvar.notify_all();
+        self.inner.terminate();
+    }
+
+    fn queued(&self) -> usize {
+        self.inner.queued()
+    }
+}
+
+impl AsTask for InFlight {
+    fn run(mut self: Box<Self>) {
+        if let Some(task) = self.task.take() {
+            HELD_SLOTS.with(|held| held.borrow_mut().push(self.slots.clone()));
+            task.run();
+            HELD_SLOTS.with(|held| held.borrow_mut().pop());
+        }
+    }
+
+    fn abort(&self, reason: Error) {
+        if let Some(task) = &self.task {
+            task.abort(reason);
+        }
+    }
+
+    fn meta(&self) -> &TaskMeta {
+        self.task.as_ref().expect("task already run.").meta()
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.slots.release();
+    }
+}
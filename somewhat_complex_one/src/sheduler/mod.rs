@@ -1,13 +1,75 @@
 #![allow(unused)]
 
+use std::{sync::{Condvar, MutexGuard}, time::{Duration, Instant}};
+
 use crate::{task::Task, AsTask};
 
 mod fifo;
+mod delay;
+mod fair;
+mod edf;
+mod layer;
 
 
+pub(crate) use delay::DelayQueue;
 pub use fifo::FifoScheduler as FifoScheduler;
+pub use fair::FairScheduler;
+pub use edf::EdfScheduler;
+pub use layer::{ConcurrencyLimitLayer, RateLimitLayer, SchedulerLayer};
+pub(crate) use layer::lend_slots;
+
+/// Waits on `condvar` until `poll` returns something, or gives up with `None` at `deadline`.
+pub(crate) fn wait_until<T, R>(
+    condvar: &Condvar,
+    mut guard: MutexGuard<'_, T>,
+    deadline: Option<Instant>,
+    mut poll: impl FnMut(&mut T) -> Option<R>,
+) -> Option<R> {
+    loop {
+        if let Some(result) = poll(&mut guard) {
+            return Some(result);
+        }
+        guard = match deadline {
+            None => condvar.wait(guard).expect("mutex poisoned."),
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    return None;
+                }
+                condvar.wait_timeout(guard, deadline - now).expect("mutex poisoned.").0
+            },
+        };
+    }
+}
+
+/// Cancels the tasks a scheduler drained on termination. Called once its lock is released,
+/// as dropping a task drops whatever it captured.
+pub(crate) fn cancel_remaining(tasks: Vec<Box<dyn AsTask>>) {
+    for task in tasks {
+        task.cancel();
+    }
+}
+
+/// What a scheduler hands to a worker asking for work.
+pub enum NextTask {
+    Task(Box<dyn AsTask>),
+    /// Nothing to run right now, the worker comes back later.
+    Idle,
+    /// The scheduler was terminated, the worker exits.
+    Shutdown,
+}
+
 pub trait Scheduler: Send + Sync {
     fn schedule(&self, task: Box<dyn AsTask>);
-    fn next_task(&self) -> Option<Box<dyn AsTask>>;
+    /// Blocks until there is a task to run, unless the scheduler decides to yield with `Idle`.
+    fn next_task(&self) -> NextTask;
+    /// Like `next_task`, but gives up with `Idle` after `timeout`. Workers waiting in
+    /// `ThreadPool::join` poll it for work, `RejectionPolicy::DiscardOldest` calls it with a zero timeout.
+    fn next_task_timeout(&self, timeout: Duration) -> NextTask;
     fn terminate(&self);
+    /// Number of tasks waiting to be handed out, used to bound the pool's queue.
+    /// Schedulers that don't track it report 0, which never counts as full.
+    fn queued(&self) -> usize {
+        0
+    }
 }
@@ -0,0 +1,50 @@
+#![allow(unused)]
+
+use std::{sync::Arc, time::Duration};
+
+use super::Task;
+
+/// Configures a task before it's committed, see `Task::builder`.
+pub struct TaskBuilder<T> {
+    task: Task<T>,
+}
+
+impl<T: Send + 'static> Task<T> {
+    pub fn builder<F>(f: F) -> TaskBuilder<T>
+    where F: FnOnce() -> T + Send + 'static {
+        TaskBuilder {
+            task: Task::from_future(Some(Box::new(f))),
+        }
+    }
+}
+
+impl<T> TaskBuilder<T> {
+    /// A name for logs and metrics, need not be unique.
+    pub fn label(mut self, label: &str) -> Self {
+        self.task.meta.label = Some(label.into());
+        self
+    }
+
+    /// Higher is more important, 0 by default.
+    pub fn priority(mut self, priority: i32) -> Self {
+        self.task.meta.priority = priority;
+        self
+    }
+
+    /// Like `ThreadPool::commit_with_timeout`.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.task.meta.timeout = Some(timeout);
+        self
+    }
+
+    /// Adds a tag, a later one with the same key takes precedence.
+    pub fn tag(mut self, key: &str, value: &str) -> Self {
+        self.task.meta.tags.retain(|(k, _)| k != key);
+        self.task.meta.tags.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn build(self) -> Task<T> {
+        self.task
+    }
+}
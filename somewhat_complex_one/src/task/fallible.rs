@@ -0,0 +1,51 @@
+#![allow(unused)]
+
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::error::{Error, TaskError};
+
+use super::{Task, TaskHandle, TaskMeta, TaskState};
+
+/// Handle to a task committed with `ThreadPool::commit_fallible`.
+pub struct FallibleHandle<T, E> {
+    inner: TaskHandle<Result<T, TaskError<E>>>,
+}
+
+impl<T, E> FallibleHandle<T, E> {
+    pub(crate) fn new(inner: TaskHandle<Result<T, TaskError<E>>>) -> Self {
+        Self { inner }
+    }
+
+    pub fn state(&self) -> TaskState {
+        self.inner.state()
+    }
+
+    pub fn meta(&self) -> &TaskMeta {
+        self.inner.meta()
+    }
+
+    pub fn cancel(&self) -> Result<(), Error> {
+        self.inner.cancel()
+    }
+
+    /// Like `TaskHandle::wait`, but tells the task's own error apart from the pool's.
+    pub fn wait(&self) -> Result<T, TaskError<E>> {
+        self.inner.wait().unwrap_or_else(|error| Err(error.into()))
+    }
+}
+
+/// Runs `f` as a task that fails when `f` returns an error or panics.
+pub(crate) fn fallible_task<T, E, F>(f: F) -> Task<Result<T, TaskError<E>>>
+where
+    T: Send + 'static,
+    E: Send + 'static,
+    F: FnOnce() -> Result<T, E> + Send + 'static {
+    let mut task = Task::from_future(Some(Box::new(move || {
+        match panic::catch_unwind(AssertUnwindSafe(f)) {
+            Ok(result) => result.map_err(TaskError::Failed),
+            Err(_) => Err(TaskError::Panicked),
+        }
+    })));
+    task.fails = Some(Result::is_err);
+    task
+}
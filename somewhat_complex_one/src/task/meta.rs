@@ -0,0 +1,86 @@
+#![allow(unused)]
+
+use std::{
+    sync::{atomic, Arc},
+    time::{Duration, Instant},
+};
+
+static NEXT_TASK_ID: atomic::AtomicU64 = atomic::AtomicU64::new(0);
+
+/// What a scheduler can know about a task without running it.
+#[derive(Debug, Clone)]
+pub struct TaskMeta {
+    pub(crate) id: u64,
+    pub(crate) submitted_at: Instant,
+    pub(crate) label: Option<Arc<str>>,
+    pub(crate) priority: i32,
+    pub(crate) tenant: Option<Arc<str>>,
+    pub(crate) deadline: Option<Instant>,
+    pub(crate) group: Option<Arc<str>>,
+    pub(crate) tags: Vec<(String, String)>,
+    pub(crate) timeout: Option<Duration>,
+}
+
+impl TaskMeta {
+    pub(crate) fn new() -> Self {
+        Self {
+            id: NEXT_TASK_ID.fetch_add(1, atomic::Ordering::Relaxed),
+            submitted_at: Instant::now(),
+            label: None,
+            priority: 0,
+            tenant: None,
+            deadline: None,
+            group: None,
+            tags: Vec::new(),
+            timeout: None,
+        }
+    }
+
+    /// Unique among all tasks of the process.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// When the task was committed.
+    pub fn submitted_at(&self) -> Instant {
+        self.submitted_at
+    }
+
+    /// See `TaskBuilder::label`.
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// Higher is more important, 0 by default.
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    /// See `ThreadPool::commit_for`.
+    pub fn tenant(&self) -> Option<&str> {
+        self.tenant.as_deref()
+    }
+
+    /// See `ThreadPool::commit_with_deadline`.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Name of the `TaskGroup` the task was committed through.
+    pub fn group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
+
+    /// How long the task may run once started, see `ThreadPool::commit_with_timeout`.
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    pub fn tag(&self, key: &str) -> Option<&str> {
+        self.tags.iter().find(|(k, _)| k == key).map(|(_, value)| value.as_str())
+    }
+
+    pub fn tags(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.tags.iter().map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+}
@@ -2,11 +2,33 @@
 
 mod task;
 mod state;
+mod recurring;
+mod retry;
+mod meta;
+mod builder;
+mod fallible;
 
 pub use task::ToTask;
-pub use task::{Task, TaskHandle};
+pub use builder::TaskBuilder;
+pub use fallible::FallibleHandle;
+pub(crate) use fallible::fallible_task;
+pub use meta::TaskMeta;
+pub use recurring::{Period, RecurringHandle};
+pub(crate) use recurring::RecurringRun;
+pub use retry::{Backoff, RetryPolicy};
+pub(crate) use retry::RetryTask;
+pub use task::{is_cancelled, Task, TaskHandle};
+pub(crate) use task::Timeout;
 pub use state::TaskState::{self, *};
+
+use crate::error::{Error, ErrorContext};
+
 pub trait AsTask: Send {
     fn run(self: Box<Self>);
-    fn cancel(&self);
+    /// Gives up on the task without running it, its handle resolves with `reason`.
+    fn abort(&self, reason: Error);
+    fn cancel(&self) {
+        self.abort(Error::Cancelled(ErrorContext::new(self.meta(), TaskState::Pending)));
+    }
+    fn meta(&self) -> &TaskMeta;
 }
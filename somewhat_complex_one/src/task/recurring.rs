@@ -0,0 +1,139 @@
+#![allow(unused)]
+
+use std::{
+    sync::{atomic, Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{error::Error, sheduler::DelayQueue};
+
+use super::{AsTask, TaskMeta};
+
+type Job = Box<dyn FnMut() -> Result<(), String> + Send>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Period {
+    /// Runs are due every `interval` measured from the previous due time,
+    /// a run that took too long is followed by the next one right away.
+    FixedRate(Duration),
+    /// Each run is due `interval` after the previous one finished.
+    FixedDelay(Duration),
+}
+
+struct Recurring {
+    job: Mutex<Job>,
+    period: Period,
+    cancelled: atomic::AtomicBool,
+    paused: atomic::AtomicBool,
+    run_count: atomic::AtomicU64,
+    last_error: Mutex<Option<String>>,
+}
+
+/// Controls a task committed with `ThreadPool::commit_periodic`.
+pub struct RecurringHandle {
+    recurring: Arc<Recurring>,
+}
+
+/// One scheduled run of a recurring task, which schedules the next one when it's done.
+pub(crate) struct RecurringRun {
+    recurring: Arc<Recurring>,
+    meta: TaskMeta,
+    delayed: Arc<DelayQueue>,
+    due: Instant,
+}
+
+impl RecurringRun {
+    pub(crate) fn new<F, E>(period: Period, job: F, delayed: Arc<DelayQueue>) -> (Self, RecurringHandle)
+    where
+        F: FnMut() -> Result<(), E> + Send + 'static,
+        E: ToString {
+        let mut job = job;
+        let recurring = Arc::new(Recurring {
+            job: Mutex::new(Box::new(move || job().map_err(|e| e.to_string()))),
+            period,
+            cancelled: atomic::AtomicBool::new(false),
+            paused: atomic::AtomicBool::new(false),
+            run_count: atomic::AtomicU64::new(0),
+            last_error: Mutex::new(None),
+        });
+
+        let run = Self {
+            recurring: recurring.clone(),
+            meta: TaskMeta::new(),
+            delayed,
+            due: Instant::now(),
+        };
+        (run, RecurringHandle { recurring })
+    }
+
+    pub(crate) fn due(&self) -> Instant {
+        self.due
+    }
+}
+
+impl AsTask for RecurringRun {
+    fn run(self: Box<Self>) {
+        let recurring = &self.recurring;
+        if recurring.cancelled.load(atomic::Ordering::Acquire) {
+            return;
+        }
+
+        if !recurring.paused.load(atomic::Ordering::Acquire) {
+            let result = (recurring.job.lock().expect("mutex poisoned."))();
+            recurring.run_count.fetch_add(1, atomic::Ordering::AcqRel);
+            if let Err(e) = result {
+                *recurring.last_error.lock().expect("mutex poisoned.") = Some(e);
+            }
+        }
+
+        let due = match recurring.period {
+            Period::FixedRate(interval) => self.due + interval,
+            Period::FixedDelay(interval) => Instant::now() + interval,
+        };
+        let delayed = self.delayed.clone();
+        delayed.schedule_at(due, Box::new(Self { due, ..*self }));
+    }
+
+    // called when the pool shuts down or rejects the run
+    fn abort(&self, _reason: Error) {
+        self.recurring.cancelled.store(true, atomic::Ordering::Release);
+    }
+
+    fn meta(&self) -> &TaskMeta {
+        &self.meta
+    }
+}
+
+impl RecurringHandle {
+    /// Stops the task for good. A run that is already executing is not interrupted.
+    pub fn cancel(&self) {
+        self.recurring.cancelled.store(true, atomic::Ordering::Release);
+    }
+
+    /// Skips runs until `resume` is called, without changing the schedule.
+    pub fn pause(&self) {
+        self.recurring.paused.store(true, atomic::Ordering::Release);
+    }
+
+    pub fn resume(&self) {
+        self.recurring.paused.store(false, atomic::Ordering::Release);
+    }
+
+    /// True once the task was cancelled, either through this handle or by the pool shutting down.
+    pub fn is_cancelled(&self) -> bool {
+        self.recurring.cancelled.load(atomic::Ordering::Acquire)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.recurring.paused.load(atomic::Ordering::Acquire)
+    }
+
+    pub fn run_count(&self) -> u64 {
+        self.recurring.run_count.load(atomic::Ordering::Acquire)
+    }
+
+    /// The error returned by the most recent failed run.
+    pub fn last_error(&self) -> Option<String> {
+        self.recurring.last_error.lock().expect("mutex poisoned.").clone()
+    }
+}
@@ -0,0 +1,166 @@
+#![allow(unused)]
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::{atomic, Arc},
+    time::{Duration, Instant},
+};
+
+use crate::{error::Error, pool::InFlightTasks, sheduler::DelayQueue};
+
+use super::{task::{self, Task}, AsTask, TaskMeta, TaskState};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backoff {
+    Fixed(Duration),
+    /// `initial * factor^(n - 1)` before the n-th retry, capped at `max`.
+    Exponential {
+        initial: Duration,
+        factor: f64,
+        max: Duration,
+    },
+}
+
+type Predicate<E> = Box<dyn Fn(&E) -> bool + Send>;
+
+pub struct RetryPolicy<E> {
+    max_attempts: u32,
+    backoff: Backoff,
+    jitter: f64,
+    predicate: Option<Predicate<E>>,
+}
+
+impl<E> RetryPolicy<E> {
+    /// Retries every error immediately, until `max_attempts` attempts have been made.
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            backoff: Backoff::Fixed(Duration::ZERO),
+            jitter: 0.0,
+            predicate: None,
+        }
+    }
+
+    /// Panics unless an exponential `factor` is finite and at least 1.
+    pub fn backoff(self, backoff: Backoff) -> Self {
+        if let Backoff::Exponential { factor, .. } = backoff {
+            assert!(factor >= 1.0 && factor.is_finite(), "backoff factor must be finite and at least 1.");
+        }
+        Self {
+            backoff,
+            ..self
+        }
+    }
+
+    /// Shortens each delay by a random fraction of up to `jitter` (clamped to `0.0..=1.0`),
+    /// so tasks failing together don't retry together.
+    pub fn jitter(self, jitter: f64) -> Self {
+        Self {
+            jitter: jitter.clamp(0.0, 1.0),
+            ..self
+        }
+    }
+
+    /// Only retries errors for which `predicate` returns true.
+    pub fn retry_if<P>(self, predicate: P) -> Self
+    where P: Fn(&E) -> bool + Send + 'static {
+        Self {
+            predicate: Some(Box::new(predicate)),
+            ..self
+        }
+    }
+
+    fn should_retry(&self, attempt: u32, error: &E) -> bool {
+        attempt < self.max_attempts
+            && self.predicate.as_ref().is_none_or(|predicate| predicate(error))
+    }
+
+    // delay before the retry following the `attempt`-th attempt
+    fn delay(&self, attempt: u32) -> Duration {
+        let delay = match self.backoff {
+            Backoff::Fixed(delay) => delay,
+            Backoff::Exponential { initial, factor, max } => {
+                let delay = initial.as_secs_f64() * factor.powi(attempt as i32 - 1);
+                Duration::from_secs_f64(delay.min(max.as_secs_f64()))
+            },
+        };
+
+        if self.jitter > 0.0 {
+            // RandomState is randomly seeded, which is all the randomness needed here
+            let random = RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+            delay.mul_f64(1.0 - self.jitter * random)
+        } else {
+            delay
+        }
+    }
+}
+
+/// A task that is re-scheduled through the delay queue after a failed attempt,
+/// instead of sleeping on the worker that ran it.
+pub(crate) struct RetryTask<T, E> {
+    task: Task<Result<T, E>>, // shell sharing its state with the handle
+    job: Box<dyn FnMut() -> Result<T, E> + Send>,
+    policy: RetryPolicy<E>,
+    delayed: Arc<DelayQueue>,
+    in_flight: Arc<InFlightTasks>, // keeps the task in flight between attempts
+}
+
+impl<T, E> RetryTask<T, E> {
+    pub(crate) fn new<F>(
+        task: Task<Result<T, E>>,
+        job: F,
+        policy: RetryPolicy<E>,
+        delayed: Arc<DelayQueue>,
+        in_flight: Arc<InFlightTasks>,
+    ) -> Self
+    where F: FnMut() -> Result<T, E> + Send + 'static {
+        Self {
+            task,
+            job: Box::new(job),
+            policy,
+            delayed,
+            in_flight,
+        }
+    }
+}
+
+impl<T, E> AsTask for RetryTask<T, E>
+where
+    T: Send + 'static,
+    E: ToString + Send + 'static {
+    fn run(mut self: Box<Self>) {
+        if self.task.cancel_flag.load(atomic::Ordering::Acquire) {
+            self.task.send(Err(Error::Cancelled(self.task.context())));
+            return;
+        }
+
+        self.task.transition_state(TaskState::Running);
+        let attempt = self.task.attempts.count.fetch_add(1, atomic::Ordering::AcqRel) + 1;
+        let result = match task::with_cancel_flag(&self.task.cancel_flag, &mut self.job) {
+            Ok(value) => Ok(value),
+            Err(e) => {
+                self.task.attempts.errors.lock().expect("mutex poisoned.").push(e.to_string());
+                if self.policy.should_retry(attempt, &e) {
+                    self.task.transition_state(TaskState::Pending);
+                    let due = Instant::now() + self.policy.delay(attempt);
+                    let delayed = self.delayed.clone();
+                    let in_flight = self.in_flight.clone();
+                    delayed.schedule_at(due, in_flight.track(self));
+                    return;
+                }
+                Err(e)
+            },
+        };
+        self.task.transition_state(TaskState::Completed);
+        self.task.send(Ok(result));
+    }
+
+    fn abort(&self, reason: Error) {
+        self.task.abort(reason);
+    }
+
+    fn meta(&self) -> &TaskMeta {
+        self.task.meta()
+    }
+}
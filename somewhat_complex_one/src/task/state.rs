@@ -6,4 +6,19 @@ pub enum TaskState {
     Running = 1,
     Completed = 2,
     Cancelled = 3,
+    /// Ran to the end with an error, see `ThreadPool::commit_fallible`.
+    Failed = 4,
+}
+
+impl TaskState {
+    pub(crate) fn from_u8(state: u8) -> Self {
+        match state {
+            0 => TaskState::Pending,
+            1 => TaskState::Running,
+            2 => TaskState::Completed,
+            3 => TaskState::Cancelled,
+            4 => TaskState::Failed,
+            _ => unreachable!(),
+        }
+    }
 }
\ No newline at end of file
@@ -1,16 +1,42 @@
 #![allow(unused)]
 
-use std::{sync::{atomic, mpsc, Arc, Mutex}};
+use std::{cell::RefCell, sync::{atomic, mpsc, Arc, Mutex}, time::{Duration, Instant}};
 
-use crate::error::Error;
+use executor::Join;
 
-use super::{AsTask, TaskState};
+use crate::{error::{Error, ErrorContext}, pool, sheduler::DelayQueue};
+
+use super::{AsTask, TaskMeta, TaskState};
+
+thread_local! {
+    // cancel flag of the task running on this thread
+    static CURRENT_CANCEL_FLAG: RefCell<Option<Arc<atomic::AtomicBool>>> = const { RefCell::new(None) };
+}
+
+/// Whether the task running on the calling thread has been asked to stop,
+/// e.g. because it timed out. Long-running tasks should check it now and then.
+pub fn is_cancelled() -> bool {
+    CURRENT_CANCEL_FLAG.with(|flag| {
+        flag.borrow().as_ref().is_some_and(|flag| flag.load(atomic::Ordering::Acquire))
+    })
+}
+
+/// Runs `f` with `flag` as the cancel flag seen by `is_cancelled`.
+pub(crate) fn with_cancel_flag<R>(flag: &Arc<atomic::AtomicBool>, f: impl FnOnce() -> R) -> R {
+    let outer = CURRENT_CANCEL_FLAG.with(|current| current.replace(Some(flag.clone())));
+    let result = f();
+    CURRENT_CANCEL_FLAG.with(|current| *current.borrow_mut() = outer);
+    result
+}
 
 pub struct TaskHandle<T> { // T
     state: Arc<atomic::AtomicU8>,
     result_receiver: Arc<Mutex<mpsc::Receiver<Result<T, Error>>>>,
     cancel_flag: Arc<atomic::AtomicBool>,
     waited: atomic::AtomicBool,
+    pub(crate) pool_id: Option<usize>, // pool whose workers run the task, see `wait`
+    attempts: Arc<Attempts>,
+    meta: TaskMeta,
 }
 
 pub struct Task<T> { // T
@@ -18,6 +44,74 @@ pub struct Task<T> { // T
     pub(crate) state: Arc<atomic::AtomicU8>,
     future: Option<Box<dyn FnOnce() -> T + Send>>,
     pub(crate) result_sender: Option<mpsc::Sender<Result<T, Error>>>, // None for unstarted
+    pub(crate) pool_id: Option<usize>, // None until committed
+    pub(crate) attempts: Arc<Attempts>,
+    pub(crate) timeout: Option<Timeout>,
+    pub(crate) meta: TaskMeta,
+    pub(crate) fails: Option<fn(&T) -> bool>, // whether a result ends the task as `Failed`
+}
+
+/// Limit on how long a task may run, enforced from the pool's timer thread.
+pub(crate) struct Timeout {
+    duration: Duration,
+    delayed: Arc<DelayQueue>,
+}
+
+impl Timeout {
+    pub(crate) fn new(duration: Duration, delayed: Arc<DelayQueue>) -> Self {
+        Self {
+            duration,
+            delayed,
+        }
+    }
+
+    // called by the task once it's running, on the worker that runs it
+    fn arm<T: Send + 'static>(&self, task: &Task<T>) -> Armed {
+        let state = task.state.clone();
+        let cancel_flag = task.cancel_flag.clone();
+        let sender = task.result_sender.clone();
+        let worker = pool::current_worker();
+        let meta = task.meta.clone();
+
+        let key = self.delayed.call_at(Instant::now() + self.duration, move || {
+            if state.load(atomic::Ordering::Acquire) != TaskState::Running as u8
+                || cancel_flag.swap(true, atomic::Ordering::AcqRel) {
+                return;
+            }
+            if let Some(sender) = sender {
+                sender.send(Err(Error::Timeout(ErrorContext::new(&meta, TaskState::Running)))).ok();
+            }
+            if let Some(worker) = worker {
+                worker.replace();
+            }
+        });
+        Armed {
+            key,
+            delayed: self.delayed.clone(),
+        }
+    }
+}
+
+/// Disarms a timeout once the task has returned or panicked, so the timer doesn't keep
+/// its result sender alive and the handle sees a panic right away.
+struct Armed {
+    key: Option<u64>,
+    delayed: Arc<DelayQueue>,
+}
+
+impl Drop for Armed {
+    fn drop(&mut self) {
+        if let Some(key) = self.key {
+            self.delayed.cancel_call(key);
+        }
+    }
+}
+
+/// How often a task has been started, and what its failed attempts returned.
+#[derive(Default)]
+pub(crate) struct Attempts {
+    pub(crate) count: atomic::AtomicU32,
+    pub(crate) errors: Mutex<Vec<String>>,
 }
 
 pub trait ToTask<T> {
@@ -28,20 +122,36 @@ pub trait ToTask<T> {
 impl<T: Send + 'static> AsTask for Task<T> {
     fn run(self: Box<Self>) {
         if self.cancel_flag.load(atomic::Ordering::Relaxed) {
-            self.result_sender.unwrap().send(Err(Error::Cancelled));
+            let context = self.context();
+            self.result_sender.unwrap().send(Err(Error::Cancelled(context)));
             return;
         }
 
         self.transition_state(TaskState::Running);
-        let result = (self.future.unwrap())();
+        self.attempts.count.fetch_add(1, atomic::Ordering::AcqRel);
+        let _armed = self.timeout.as_ref().map(|timeout| timeout.arm(&self));
+        let result = with_cancel_flag(&self.cancel_flag, self.future.unwrap());
+        let state = if self.fails.is_some_and(|fails| fails(&result)) {
+            if let Some(pool) = pool::current_pool().filter(|pool| Some(pool.id) == self.pool_id) {
+                pool.failed.fetch_add(1, atomic::Ordering::Relaxed);
+            }
+            TaskState::Failed
+        } else {
+            TaskState::Completed
+        };
+        // final state before the result is out, so a returned `wait` always sees it
+        self.state.store(state as u8, atomic::Ordering::Release);
         self.result_sender.unwrap().send(Ok(result)).ok();
-        self.state.store(TaskState::Completed as u8, atomic::Ordering::Release);
     }
     
-    fn cancel(&self) {
+    fn abort(&self, reason: Error) {
         self.cancel_flag.store(true, atomic::Ordering::Release);
         self.transition_state(TaskState::Cancelled);
-        self.result_sender.as_ref().unwrap().send(Err(Error::Cancelled));
+        self.send(Err(reason));
+    }
+
+    fn meta(&self) -> &TaskMeta {
+        &self.meta
     }
 }
 
@@ -50,12 +160,7 @@ where
     F: FnOnce() -> T + Send + 'static,
     T: Send {
     fn to_task(self) -> Option<Task<T>> {
-        Some(Task::<T> {
-            result_sender: None,
-            cancel_flag: Arc::new(atomic::AtomicBool::new(false)),
-            state: Arc::new(atomic::AtomicU8::new(TaskState::Pending as u8)),
-            future: Some(Box::new(self)),
-        })
+        Some(Task::from_future(Some(Box::new(self))))
     }
 }
 
@@ -74,6 +179,33 @@ impl<T> Task<T> {
         }
     }
 
+    /// A task without a future is only a shell for the state shared with its handle,
+    /// for wrappers that run their own job.
+    pub(crate) fn from_future(future: Option<Box<dyn FnOnce() -> T + Send>>) -> Self {
+        Self {
+            result_sender: None,
+            pool_id: None,
+            cancel_flag: Arc::new(atomic::AtomicBool::new(false)),
+            state: Arc::new(atomic::AtomicU8::new(TaskState::Pending as u8)),
+            attempts: Arc::new(Attempts::default()),
+            timeout: None,
+            meta: TaskMeta::new(),
+            fails: None,
+            future,
+        }
+    }
+
+    /// Delivers the outcome to the task's handle.
+    pub(crate) fn send(&self, result: Result<T, Error>) {
+        if let Some(sender) = &self.result_sender {
+            sender.send(result).ok();
+        }
+    }
+
+    pub(crate) fn context(&self) -> ErrorContext {
+        ErrorContext::new(&self.meta, TaskState::from_u8(self.state.load(atomic::Ordering::Acquire)))
+    }
+
     pub(crate) fn transition_state(&self, new_state: TaskState) {
         self.state.store(new_state as u8, atomic::Ordering::Release);
     }
@@ -86,22 +218,43 @@ impl<T> TaskHandle<T> {
             cancel_flag: task.cancel_flag.clone(),
             state: task.state.clone(),
             waited: atomic::AtomicBool::new(false),
+            pool_id: task.pool_id,
+            attempts: task.attempts.clone(),
+            meta: task.meta.clone(),
             result_receiver: Arc::new(Mutex::new(result_receiver)),
         }
     }
 
+    /// Whether the task ran to the end, successfully or not.
     pub fn has_finished(&self) -> bool {
-        self.state.load(atomic::Ordering::Acquire) == TaskState::Completed as u8
+        matches!(self.state(), TaskState::Completed | TaskState::Failed)
     }
 
     pub fn state(&self) -> TaskState {
-        match self.state.load(atomic::Ordering::Acquire) {
-            0 => TaskState::Pending,
-            1 => TaskState::Running,
-            2 => TaskState::Completed,
-            3 => TaskState::Cancelled,
-            _ => unreachable!(),
-        }
+        TaskState::from_u8(self.state.load(atomic::Ordering::Acquire))
+    }
+
+    fn context(&self) -> ErrorContext {
+        ErrorContext::new(&self.meta, self.state())
+    }
+
+    /// Name of the `TaskGroup` the task was committed through.
+    pub fn group(&self) -> Option<&str> {
+        self.meta.group()
+    }
+
+    pub fn meta(&self) -> &TaskMeta {
+        &self.meta
+    }
+
+    /// How many times the task has been started, more than once only for retried tasks.
+    pub fn attempts(&self) -> u32 {
+        self.attempts.count.load(atomic::Ordering::Acquire)
+    }
+
+    /// Errors returned by the failed attempts of a retried task, oldest first.
+    pub fn errors(&self) -> Vec<String> {
+        self.attempts.errors.lock().expect("mutex poisoned.").clone()
     }
 
     pub fn wait(&self) -> Result<T, Error> {
@@ -109,21 +262,57 @@ impl<T> TaskHandle<T> {
             return Err(Error::MultipleWaits);
         }
 
+        // waiting on a worker of the task's own pool takes that worker out of service
+        let _blocked = match pool::current_pool() {
+            Some(pool) if Some(pool.id) == self.pool_id => {
+                Some(pool::Blocked::enter(pool, self.state() == TaskState::Pending)?)
+            },
+            _ => None,
+        };
+
         self.waited.store(true, atomic::Ordering::Relaxed);
-        if self.cancel_flag.load(atomic::Ordering::Acquire) {
-            return Err(Error::Cancelled);            
+        // only a task that won't run returns early, one that is merely asked to stop
+        // still delivers its result
+        if self.state() == TaskState::Cancelled {
+            // a more specific reason, e.g. a rejection, may already be waiting
+            return match self.result_receiver.lock().unwrap().try_recv() {
+                Ok(Err(e)) => Err(e),
+                _ => Err(Error::Cancelled(self.context())),
+            };
         }
         match self.result_receiver.lock().unwrap().recv() {
             r@Ok(..) => r.unwrap(),
-            Err(e) => {
-                match self.state.load(atomic::Ordering::Acquire) {
-                    3 => Err(Error::Cancelled),
-                    _ => Err(Error::ChannelDisconnected),
-                }
-            }
+            Err(e) => Err(self.lost()),
+        }
+    }
+
+    /// Like `wait`, but returns `None` instead of blocking while the result isn't out yet.
+    pub(crate) fn try_wait(&self) -> Option<Result<T, Error>> {
+        let result = match self.result_receiver.lock().unwrap().try_recv() {
+            Ok(result) => result,
+            Err(mpsc::TryRecvError::Disconnected) => Err(self.lost()),
+            Err(mpsc::TryRecvError::Empty) if self.state() == TaskState::Cancelled => {
+                Err(Error::Cancelled(self.context()))
+            },
+            Err(mpsc::TryRecvError::Empty) => return None,
+        };
+        self.waited.store(true, atomic::Ordering::Relaxed);
+        Some(result)
+    }
+
+    // why no result will ever arrive
+    fn lost(&self) -> Error {
+        match self.state() {
+            TaskState::Cancelled => Error::Cancelled(self.context()),
+            _ => Error::ChannelDisconnected(self.context()),
         }
     }
 
+    /// Raises the signal read by `is_cancelled`, without touching the task's state.
+    pub(crate) fn request_stop(&self) {
+        self.cancel_flag.store(true, atomic::Ordering::Release);
+    }
+
     /// If a task has already been running, then this method can't really cancel it.
     /// For such operations, you should implement the mechanism by your own.
     pub fn cancel(&self) -> Result<(), Error> {
@@ -132,7 +321,15 @@ impl<T> TaskHandle<T> {
             self.state.store(TaskState::Cancelled as u8, atomic::Ordering::Release);
             Ok(())
         } else {
-            Err(Error::CancelAfterRunning)
+            Err(Error::CancelAfterRunning(self.context()))
         }
     }
+}
+
+impl<T> Join<T> for TaskHandle<T> {
+    type Error = Error;
+
+    fn join(self) -> Result<T, Error> {
+        self.wait()
+    }
 }
\ No newline at end of file
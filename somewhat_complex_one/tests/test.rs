@@ -28,7 +28,7 @@ mod complex_tests {
             counter_clone.fetch_add(1, Ordering::SeqCst);
         });
 
-        thread::sleep(Duration::from_millis(100)); // 等待任务执行完成
+        pool.wait_idle(); // 等待任务执行完成
         assert_eq!(counter.load(Ordering::SeqCst), 1);
     }
 
@@ -71,7 +71,7 @@ mod complex_tests {
             });
         }
 
-        thread::sleep(Duration::from_millis(500)); // 等待所有任务执行完成
+        pool.wait_idle(); // 等待所有任务执行完成
         assert_eq!(counter.load(Ordering::SeqCst), 1000);
     }
 
@@ -401,7 +401,7 @@ mod complex_tests {
         });
 
         // 等待线程完成
-        let cancel_result = cancel_thread.join().unwrap();
+        cancel_thread.join().unwrap();
         let wait_result = wait_thread.join().unwrap();
 
         // 检查任务状态
@@ -463,4 +463,1053 @@ mod complex_tests {
         assert_eq!(result, Err("Task failed".to_string())); // 任务应返回错误
         assert_eq!(handle.state(), TaskState::Completed); // 任务状态应为已完成
     }
-}
\ No newline at end of file
+
+    fn fib(pool: &Arc<ThreadPool>, n: u64) -> u64 {
+        if n < 2 {
+            return n;
+        }
+        let pool_clone = pool.clone();
+        let (a, b) = pool.join(|| fib(pool, n - 1), move || fib(&pool_clone, n - 2));
+        a + b.unwrap()
+    }
+
+    #[test]
+    fn test_join_recursive_on_single_worker() {
+        let pool = Arc::new(ThreadPool::new()
+            .num_threads(1)
+            .build()
+            .expect("Failed to create thread pool"));
+
+        // 在唯一的 worker 上递归 join，不应死锁
+        let pool_clone = pool.clone();
+        let handle = pool.commit(move || fib(&pool_clone, 12));
+        assert_eq!(handle.wait(), Ok(144));
+
+        // 在非 worker 线程上 join
+        let (a, b) = pool.join(|| 1, || 2);
+        assert_eq!((a, b), (1, Ok(2)));
+    }
+
+    #[test]
+    fn test_join_when_b_panics() {
+        let pool = Arc::new(ThreadPool::new()
+            .num_threads(2)
+            .build()
+            .expect("Failed to create thread pool"));
+
+        // b 在另一个 worker 上 panic, join 应返回错误而不是一直等待
+        let pool_clone = pool.clone();
+        let handle = pool.commit(move || {
+            let (a, b) = pool_clone.join(
+                || {
+                    thread::sleep(Duration::from_millis(50)); // 让另一个 worker 先取走 b
+                    1
+                },
+                || -> usize { panic!("task panicked!") },
+            );
+            (a, matches!(b, Err(Error::ChannelDisconnected(_))))
+        });
+        assert_eq!(handle.wait(), Ok((1, true)));
+    }
+
+    #[test]
+    fn test_nested_wait_would_deadlock() {
+        let pool = Arc::new(ThreadPool::new()
+            .num_threads(1)
+            .build()
+            .expect("Failed to create thread pool"));
+
+        // 唯一的 worker 等待排在自己后面的任务，应报错而不是挂起
+        let pool_clone = pool.clone();
+        let handle = pool.commit(move || {
+            let inner = pool_clone.commit(|| 42);
+            inner.wait()
+        });
+        assert_eq!(handle.wait(), Ok(Err(Error::WouldDeadlock)));
+    }
+
+    #[test]
+    fn test_delayed_task() {
+        let pool = ThreadPool::new()
+            .num_threads(1)
+            .build()
+            .expect("Failed to create thread pool");
+
+        let start = std::time::Instant::now();
+        let delayed = pool.commit_after(Duration::from_millis(300), || 1);
+        let cancelled = pool.commit_at(start + Duration::from_millis(300), || 2);
+        assert_eq!(delayed.state(), TaskState::Pending);
+
+        // 延迟任务不应占用 worker
+        let immediate = pool.commit(|| 3);
+        assert_eq!(immediate.wait(), Ok(3));
+        assert!(start.elapsed() < Duration::from_millis(300));
+
+        assert!(cancelled.cancel().is_ok());
+        assert_eq!(delayed.wait(), Ok(1));
+        assert!(start.elapsed() >= Duration::from_millis(300));
+        assert!(cancelled.wait().is_err());
+    }
+
+    #[test]
+    fn test_periodic_task() {
+        let pool = ThreadPool::new()
+            .num_threads(2)
+            .build()
+            .expect("Failed to create thread pool");
+
+        let counter = Arc::new(AtomicUsize::new(0));
+        let counter_clone = counter.clone();
+        let handle = pool.commit_periodic(Period::FixedRate(Duration::from_millis(50)), move || {
+            let count = counter_clone.fetch_add(1, Ordering::SeqCst) + 1;
+            if count == 2 { Err(format!("run {} failed", count)) } else { Ok(()) }
+        });
+
+        thread::sleep(Duration::from_millis(220));
+        assert!(handle.run_count() >= 3);
+        assert_eq!(handle.last_error(), Some("run 2 failed".to_string()));
+
+        // 暂停期间不应执行
+        handle.pause();
+        thread::sleep(Duration::from_millis(60));
+        let paused_count = handle.run_count();
+        thread::sleep(Duration::from_millis(150));
+        assert_eq!(handle.run_count(), paused_count);
+
+        handle.resume();
+        thread::sleep(Duration::from_millis(150));
+        assert!(handle.run_count() > paused_count);
+
+        // 线程池关闭后任务自动停止
+        drop(pool);
+        assert!(handle.is_cancelled());
+        let final_count = handle.run_count();
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(handle.run_count(), final_count);
+    }
+
+    #[test]
+    fn test_retry_with_backoff() {
+        let pool = ThreadPool::new()
+            .num_threads(1)
+            .build()
+            .expect("Failed to create thread pool");
+
+        let counter = Arc::new(AtomicUsize::new(0));
+        let counter_clone = counter.clone();
+        let policy = RetryPolicy::new(5)
+            .backoff(Backoff::Exponential {
+                initial: Duration::from_millis(100),
+                factor: 2.0,
+                max: Duration::from_secs(1),
+            })
+            .jitter(0.1);
+        let handle = pool.commit_with_retry(policy, move || {
+            match counter_clone.fetch_add(1, Ordering::SeqCst) {
+                n if n < 2 => Err(format!("attempt {} failed", n + 1)),
+                n => Ok(n + 1),
+            }
+        });
+
+        // 重试等待期间 worker 应空闲
+        let start = std::time::Instant::now();
+        assert_eq!(pool.commit(|| 1).wait(), Ok(1));
+        assert!(start.elapsed() < Duration::from_millis(100));
+
+        assert_eq!(handle.wait(), Ok(Ok(3)));
+        assert_eq!(handle.attempts(), 3);
+        assert_eq!(handle.errors(), vec!["attempt 1 failed", "attempt 2 failed"]);
+
+        // 谓词拒绝的错误不重试
+        let policy = RetryPolicy::new(5).retry_if(|e: &String| e != "fatal");
+        let handle = pool.commit_with_retry(policy, || -> Result<(), String> { Err("fatal".to_string()) });
+        assert_eq!(handle.wait(), Ok(Err("fatal".to_string())));
+        assert_eq!(handle.attempts(), 1);
+    }
+
+    #[test]
+    fn test_task_timeout() {
+        let pool = ThreadPool::new()
+            .num_threads(1)
+            .replace_stuck_workers(true)
+            .build()
+            .expect("Failed to create thread pool");
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let start = std::time::Instant::now();
+        let handle = pool.commit_with_timeout(Duration::from_millis(100), move || {
+            while !is_cancelled() {
+                thread::sleep(Duration::from_millis(10));
+            }
+            sender.send("Task asked to stop").unwrap();
+            thread::sleep(Duration::from_millis(500)); // 模拟无法立即退出的任务
+        });
+
+        // 比较错误上下文时不考虑耗时
+        let context = ErrorContext {
+            task_id: handle.meta().id(),
+            label: None,
+            state: TaskState::Running,
+            elapsed: Duration::ZERO,
+        };
+        assert_eq!(handle.wait(), Err(Error::Timeout(context)));
+        assert!(start.elapsed() < Duration::from_millis(500));
+        assert_eq!(receiver.recv(), Ok("Task asked to stop"));
+
+        // 卡住的 worker 已被替换，新任务无需等待
+        assert_eq!(pool.commit(|| 42).wait(), Ok(42));
+        assert!(start.elapsed() < Duration::from_millis(500));
+
+        // 未超时的任务正常完成
+        let handle = pool.commit_with_timeout(Duration::from_secs(1), || 1);
+        assert_eq!(handle.wait(), Ok(1));
+
+        // 被替换的 worker 退出后不再计入
+        thread::sleep(Duration::from_millis(700));
+        assert_eq!(pool.metrics().workers, 1);
+    }
+
+    // 单个 worker 被阻塞、队列容量为 1 的线程池，返回的 sender 用于放行 worker
+    fn saturated_pool(policy: RejectionPolicy) -> (ThreadPool, std::sync::mpsc::Sender<()>, TaskHandle<i32>) {
+        let pool = ThreadPool::new()
+            .num_threads(1)
+            .queue_capacity(1)
+            .rejection_policy(policy)
+            .build()
+            .expect("Failed to create thread pool");
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let (started_sender, started_receiver) = std::sync::mpsc::channel();
+        pool.commit(move || {
+            started_sender.send(()).unwrap();
+            receiver.recv().unwrap();
+        });
+        started_receiver.recv().unwrap();
+        let queued = pool.commit(|| 1);
+        (pool, sender, queued)
+    }
+
+    #[test]
+    fn test_rejection_policies() {
+        let (pool, release, queued) = saturated_pool(RejectionPolicy::Abort);
+        assert_eq!(pool.try_commit(|| 2).err(), Some(Error::Rejected));
+        assert_eq!(pool.commit(|| 2).wait(), Err(Error::Rejected));
+        release.send(()).unwrap();
+        assert_eq!(queued.wait(), Ok(1));
+
+        let (pool, release, queued) = saturated_pool(RejectionPolicy::CallerRuns);
+        let caller = thread::current().id();
+        let handle = pool.commit(move || thread::current().id() == caller);
+        assert_eq!(handle.wait(), Ok(true));
+        release.send(()).unwrap();
+        assert_eq!(queued.wait(), Ok(1));
+
+        let (pool, release, queued) = saturated_pool(RejectionPolicy::DiscardOldest);
+        let handle = pool.commit(|| 2);
+        assert!(matches!(queued.wait(), Err(Error::Cancelled(_))));
+        release.send(()).unwrap();
+        assert_eq!(handle.wait(), Ok(2));
+
+        let (pool, release, queued) = saturated_pool(RejectionPolicy::Discard);
+        let handle = pool.commit(|| 2);
+        assert!(matches!(handle.wait(), Err(Error::Cancelled(_))));
+        release.send(()).unwrap();
+        assert_eq!(queued.wait(), Ok(1));
+    }
+
+    #[test]
+    fn test_commit_blocking() {
+        // 没有阻塞线程的线程池无法执行 commit_blocking 的任务
+        assert!(ThreadPool::new().max_blocking_threads(0).build().is_err());
+
+        let pool = ThreadPool::new()
+            .num_threads(1)
+            .max_blocking_threads(2)
+            .build()
+            .expect("Failed to create thread pool");
+
+        let blocking: Vec<_> = (0..3)
+            .map(|i| pool.commit_blocking(move || {
+                thread::sleep(Duration::from_millis(300)); // 模拟阻塞 I/O
+                i
+            }))
+            .collect();
+
+        // 计算任务不会排在阻塞任务之后
+        let start = std::time::Instant::now();
+        assert_eq!(pool.commit(|| 42).wait(), Ok(42));
+        assert!(start.elapsed() < Duration::from_millis(300));
+
+        thread::sleep(Duration::from_millis(100));
+        let metrics = pool.metrics();
+        assert_eq!(metrics.workers, 1);
+        assert_eq!(metrics.blocking_workers, 2);
+        assert_eq!(metrics.active_blocking_workers, 2);
+        assert_eq!(metrics.blocking_queued, 1);
+
+        let results: Vec<_> = blocking.iter().map(|handle| handle.wait()).collect();
+        assert_eq!(results, vec![Ok(0), Ok(1), Ok(2)]);
+    }
+
+    #[test]
+    fn test_task_group_cancel_all() {
+        let pool = ThreadPool::new()
+            .num_threads(1)
+            .build()
+            .expect("Failed to create thread pool");
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let group = pool.group("request-1");
+        group.commit(move || {
+            sender.send(()).unwrap();
+            while !is_cancelled() {
+                thread::sleep(Duration::from_millis(10));
+            }
+            1
+        });
+        group.commit(|| 2);
+        group.commit(|| 3);
+
+        // 第一个任务运行中，其余仍在排队
+        receiver.recv().unwrap();
+        group.cancel_all();
+
+        let results = group.wait_all();
+        assert!(matches!(results[..], [Ok(1), Err(Error::Cancelled(_)), Err(Error::Cancelled(_))]));
+
+        let other = pool.group("request-2");
+        other.commit(|| 4);
+        assert_eq!(other.name(), "request-2");
+        assert_eq!(other.wait_all(), vec![Ok(4)]);
+    }
+
+    #[test]
+    fn test_fair_scheduler() {
+        let pool = ThreadPool::new()
+            .num_threads(1)
+            .scheduler(FairScheduler::new().weight("quiet", 2))
+            .build()
+            .expect("Failed to create thread pool");
+
+        // 先阻塞 worker，让所有任务都进入队列
+        let (sender, receiver) = std::sync::mpsc::channel::<()>();
+        pool.commit_for("gate", move || receiver.recv().unwrap());
+
+        let order = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut handles = Vec::new();
+        for tenant in std::iter::repeat_n("noisy", 20).chain(std::iter::repeat_n("quiet", 4)) {
+            let order_clone = order.clone();
+            handles.push(pool.commit_for(tenant, move || order_clone.lock().unwrap().push(tenant)));
+        }
+        sender.send(()).unwrap();
+        handles.into_iter().for_each(|handle| handle.wait().unwrap());
+
+        // 轮转调度：quiet 的任务不必等 noisy 的 20 个任务全部执行完
+        let order = order.lock().unwrap();
+        assert_eq!(order[..6], ["noisy", "quiet", "quiet", "noisy", "quiet", "quiet"]);
+    }
+
+    #[test]
+    fn test_fair_scheduler_concurrency_cap() {
+        let pool = ThreadPool::new()
+            .num_threads(4)
+            .scheduler(FairScheduler::new().max_concurrency("capped", 1))
+            .build()
+            .expect("Failed to create thread pool");
+
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let running = running.clone();
+                let max_running = max_running.clone();
+                pool.commit_for("capped", move || {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_running.fetch_max(now, Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(50));
+                    running.fetch_sub(1, Ordering::SeqCst);
+                })
+            })
+            .collect();
+
+        // 其他租户不受限制
+        assert_eq!(pool.commit_for("other", || 1).wait(), Ok(1));
+        handles.into_iter().for_each(|handle| handle.wait().unwrap());
+        assert_eq!(max_running.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_edf_scheduler() {
+        let pool = ThreadPool::new()
+            .num_threads(1)
+            .scheduler(EdfScheduler::new().drop_missed(true))
+            .build()
+            .expect("Failed to create thread pool");
+
+        // 没有截止时间的任务排在最后，等它开始运行后再提交其他任务
+        let (sender, receiver) = std::sync::mpsc::channel::<()>();
+        let (started_sender, started_receiver) = std::sync::mpsc::channel();
+        pool.commit(move || {
+            started_sender.send(()).unwrap();
+            receiver.recv().unwrap();
+        });
+        started_receiver.recv().unwrap();
+
+        let now = std::time::Instant::now();
+        let order = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut handles = Vec::new();
+        for (i, ms) in [(0, 300), (1, 200), (2, 100)] {
+            let order_clone = order.clone();
+            handles.push(pool.commit_with_deadline(now + Duration::from_millis(ms), move || order_clone.lock().unwrap().push(i)));
+        }
+        let stale = pool.commit_with_deadline(now + Duration::from_millis(10), || 42);
+
+        // 阻塞期间 stale 的截止时间已过
+        thread::sleep(Duration::from_millis(50));
+        sender.send(()).unwrap();
+
+        let context = ErrorContext {
+            task_id: stale.meta().id(),
+            label: None,
+            state: TaskState::Pending,
+            elapsed: Duration::ZERO,
+        };
+        assert_eq!(stale.wait(), Err(Error::DeadlineMissed(context)));
+        handles.into_iter().for_each(|handle| handle.wait().unwrap());
+        assert_eq!(*order.lock().unwrap(), vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn test_scheduler_layers() {
+        let pool = ThreadPool::new()
+            .num_threads(4)
+            .layer(ConcurrencyLimitLayer::new(1))
+            .build()
+            .expect("Failed to create thread pool");
+
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let running = running.clone();
+                let max_running = max_running.clone();
+                pool.commit(move || {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_running.fetch_max(now, Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(20));
+                    running.fetch_sub(1, Ordering::SeqCst);
+                })
+            })
+            .collect();
+        handles.into_iter().for_each(|handle| handle.wait().unwrap());
+        assert_eq!(max_running.load(Ordering::SeqCst), 1);
+
+        // 每秒 20 个，突发 1 个：5 个任务至少需要 200ms
+        let pool = ThreadPool::new()
+            .num_threads(4)
+            .layer(RateLimitLayer::new(20.0, 1))
+            .build()
+            .expect("Failed to create thread pool");
+
+        let start = std::time::Instant::now();
+        let handles: Vec<_> = (0..5).map(|i| pool.commit(move || i)).collect();
+        handles.into_iter().for_each(|handle| { handle.wait().unwrap(); });
+        assert!(start.elapsed() >= Duration::from_millis(190));
+    }
+
+    #[test]
+    #[should_panic(expected = "rate limit must be positive and finite.")]
+    fn test_rate_limit_layer_rejects_zero_rate() {
+        RateLimitLayer::new(0.0, 1);
+    }
+
+    #[test]
+    fn test_join_under_concurrency_limit() {
+        let pool = Arc::new(ThreadPool::new()
+            .num_threads(2)
+            .layer(ConcurrencyLimitLayer::new(1))
+            .build()
+            .expect("Failed to create thread pool"));
+
+        // join 中的 worker 让出自己的名额, 否则 b 永远拿不到名额
+        let pool_clone = pool.clone();
+        let handle = pool.commit(move || pool_clone.join(|| 1, || 2));
+        assert_eq!(handle.wait(), Ok((1, Ok(2))));
+    }
+
+    // 记录每个任务元数据的调度器
+    struct RecordingScheduler {
+        inner: FifoScheduler,
+        seen: Arc<std::sync::Mutex<Vec<TaskMeta>>>,
+    }
+
+    impl Scheduler for RecordingScheduler {
+        fn schedule(&self, task: Box<dyn AsTask>) {
+            self.seen.lock().unwrap().push(task.meta().clone());
+            self.inner.schedule(task);
+        }
+
+        fn next_task(&self) -> NextTask {
+            self.inner.next_task()
+        }
+
+        fn next_task_timeout(&self, timeout: Duration) -> NextTask {
+            self.inner.next_task_timeout(timeout)
+        }
+
+        fn terminate(&self) {
+            self.inner.terminate();
+        }
+    }
+
+    #[test]
+    fn test_task_meta_visible_to_scheduler() {
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let pool = ThreadPool::new()
+            .num_threads(1)
+            .scheduler(RecordingScheduler { inner: FifoScheduler::new(), seen: seen.clone() })
+            .build()
+            .expect("Failed to create thread pool");
+
+        let before = std::time::Instant::now();
+        let first = pool.commit(|| 1);
+        let second = pool.commit_for("tenant-a", || 2);
+        assert_eq!(first.wait(), Ok(1));
+        assert_eq!(second.wait(), Ok(2));
+
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), 2);
+        assert_eq!(seen[0].id(), first.meta().id());
+        assert!(seen[0].id() < seen[1].id());
+        assert!(seen[0].submitted_at() >= before);
+        assert_eq!(seen[0].tenant(), None);
+        assert_eq!(seen[1].tenant(), Some("tenant-a"));
+        assert_eq!(seen[1].priority(), 0);
+        assert_eq!(seen[1].tags().count(), 0);
+    }
+
+    // 每次最多等待 10ms，超时就让 worker 空转一次
+    struct YieldingScheduler {
+        inner: FifoScheduler,
+        idles: Arc<AtomicUsize>,
+    }
+
+    impl Scheduler for YieldingScheduler {
+        fn schedule(&self, task: Box<dyn AsTask>) {
+            self.inner.schedule(task);
+        }
+
+        fn next_task(&self) -> NextTask {
+            match self.inner.next_task_timeout(Duration::from_millis(10)) {
+                NextTask::Idle => {
+                    self.idles.fetch_add(1, Ordering::SeqCst);
+                    NextTask::Idle
+                },
+                next => next,
+            }
+        }
+
+        fn next_task_timeout(&self, timeout: Duration) -> NextTask {
+            self.inner.next_task_timeout(timeout)
+        }
+
+        fn terminate(&self) {
+            self.inner.terminate();
+        }
+    }
+
+    #[test]
+    fn test_idle_scheduler_keeps_workers() {
+        let scheduler = FifoScheduler::new();
+        assert!(matches!(scheduler.next_task_timeout(Duration::from_millis(10)), NextTask::Idle));
+        scheduler.terminate();
+        assert!(matches!(scheduler.next_task_timeout(Duration::from_millis(10)), NextTask::Shutdown));
+
+        let idles = Arc::new(AtomicUsize::new(0));
+        let pool = ThreadPool::new()
+            .num_threads(1)
+            .scheduler(YieldingScheduler { inner: FifoScheduler::new(), idles: idles.clone() })
+            .build()
+            .expect("Failed to create thread pool");
+
+        // worker 空闲时返回 Idle 不应退出
+        thread::sleep(Duration::from_millis(100));
+        assert!(idles.load(Ordering::SeqCst) > 0);
+        assert_eq!(pool.commit(|| 42).wait(), Ok(42));
+    }
+
+    // 通过 /proc/self/task/<tid>/status 读取当前线程允许运行的 CPU
+    #[cfg(target_os = "linux")]
+    fn current_thread_cpus() -> String {
+        let thread_self = std::fs::read_link("/proc/thread-self").unwrap();
+        let tid = thread_self.file_name().unwrap().to_str().unwrap().to_string();
+        let status = std::fs::read_to_string(format!("/proc/self/task/{}/status", tid)).unwrap();
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix("Cpus_allowed_list:"))
+            .unwrap()
+            .trim()
+            .to_string()
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_worker_affinity() {
+        // 取当前允许的第一个核心, 容器的 cpuset 不一定包含 0 号核心
+        let allowed = current_thread_cpus();
+        let first = allowed.split([',', '-']).next().unwrap().to_string();
+        let pool = ThreadPool::new()
+            .num_threads(2)
+            .affinity(CoreSelection::Cores(vec![first.parse().unwrap()]))
+            .build()
+            .expect("Failed to create thread pool");
+
+        let handles: Vec<_> = (0..2).map(|_| pool.commit(current_thread_cpus)).collect();
+        for handle in handles {
+            assert_eq!(handle.wait(), Ok(first.clone()));
+        }
+
+        // 轮转和按物理核心分配都应能正常启动
+        for selection in [CoreSelection::RoundRobin, CoreSelection::PhysicalCores] {
+            let pool = ThreadPool::new()
+                .num_threads(2)
+                .affinity(selection)
+                .build()
+                .expect("Failed to create thread pool");
+            let cpus = pool.commit(current_thread_cpus).wait().unwrap();
+            assert!(!cpus.contains(',') && !cpus.contains('-'));
+        }
+    }
+
+    #[test]
+    fn test_wait_idle() {
+        let pool = ThreadPool::new()
+            .num_threads(2)
+            .build()
+            .expect("Failed to create thread pool");
+        let counter = Arc::new(AtomicUsize::new(0));
+
+        // 空闲的线程池立即返回
+        assert!(pool.wait_idle_timeout(Duration::ZERO));
+
+        for _ in 0..4 {
+            let counter_clone = counter.clone();
+            pool.commit(move || {
+                thread::sleep(Duration::from_millis(100));
+                counter_clone.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+        let counter_clone = counter.clone();
+        pool.commit_blocking(move || {
+            thread::sleep(Duration::from_millis(100));
+            counter_clone.fetch_add(1, Ordering::SeqCst);
+        });
+
+        assert!(!pool.wait_idle_timeout(Duration::from_millis(10)));
+        pool.wait_idle();
+        assert_eq!(counter.load(Ordering::SeqCst), 5);
+
+        // 被取消而未运行的任务同样不再计入
+        let _gate = pool.commit(|| thread::sleep(Duration::from_millis(100)));
+        let _gate2 = pool.commit(|| thread::sleep(Duration::from_millis(100)));
+        let queued = pool.commit(|| ());
+        queued.cancel().unwrap();
+        assert!(pool.wait_idle_timeout(Duration::from_secs(5)));
+
+        // 线程池在等待后仍可继续使用
+        assert_eq!(pool.commit(|| 1).wait(), Ok(1));
+    }
+
+    // 与具体执行器无关的代码
+    fn sum_squares<E: Executor>(executor: &E, n: usize) -> usize {
+        let handles: Vec<_> = (0..n).map(|i| executor.submit(move || i * i)).collect();
+        handles.into_iter().map(|handle| handle.join().unwrap()).sum()
+    }
+
+    #[test]
+    fn test_executor_trait() {
+        let pool = ThreadPool::new()
+            .num_threads(4)
+            .build()
+            .expect("Failed to create thread pool");
+        assert_eq!(sum_squares(&pool, 10), 285);
+        assert_eq!(sum_squares(&InlineExecutor, 10), 285);
+
+        let counter = Arc::new(AtomicUsize::new(0));
+        let counter_clone = counter.clone();
+        Executor::execute(&pool, move || {
+            counter_clone.fetch_add(1, Ordering::SeqCst);
+        });
+        pool.wait_idle();
+        Executor::shutdown(pool);
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_task_builder() {
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let pool = ThreadPool::new()
+            .num_threads(1)
+            .scheduler(RecordingScheduler { inner: FifoScheduler::new(), seen: seen.clone() })
+            .build()
+            .expect("Failed to create thread pool");
+
+        let task = Task::builder(|| 42)
+            .label("resize")
+            .priority(5)
+            .timeout(Duration::from_secs(5))
+            .tag("tenant", "a")
+            .build();
+        let handle = pool.commit(task);
+        assert_eq!(handle.wait(), Ok(42));
+
+        // 句柄与调度器看到相同的属性
+        for meta in [handle.meta().clone(), seen.lock().unwrap()[0].clone()] {
+            assert_eq!(meta.label(), Some("resize"));
+            assert_eq!(meta.priority(), 5);
+            assert_eq!(meta.timeout(), Some(Duration::from_secs(5)));
+            assert_eq!(meta.tag("tenant"), Some("a"));
+        }
+
+        // 通过构建器设置的超时同样生效
+        let task = Task::builder(|| {
+            while !is_cancelled() {
+                thread::sleep(Duration::from_millis(10));
+            }
+        })
+        .timeout(Duration::from_millis(50))
+        .build();
+        assert!(matches!(pool.commit(task).wait(), Err(Error::Timeout(_))));
+    }
+
+    #[test]
+    fn test_commit_fallible() {
+        let pool = ThreadPool::new()
+            .num_threads(1)
+            .build()
+            .expect("Failed to create thread pool");
+
+        let ok = pool.commit_fallible(|| Ok::<_, String>(1));
+        let failed = pool.commit_fallible(|| Err::<usize, _>("disk full".to_string()));
+        let panicked = pool.commit_fallible(|| -> Result<usize, String> { panic!("task panicked!") });
+
+        assert_eq!(ok.wait(), Ok(1));
+        assert_eq!(ok.state(), TaskState::Completed);
+        assert_eq!(failed.wait(), Err(TaskError::Failed("disk full".to_string())));
+        assert_eq!(failed.state(), TaskState::Failed);
+        assert_eq!(panicked.wait(), Err(TaskError::Panicked));
+        assert_eq!(panicked.state(), TaskState::Failed);
+
+        // 排队中被取消的任务与失败区分开
+        let (sender, receiver) = std::sync::mpsc::channel::<()>();
+        let _gate = pool.commit(move || receiver.recv());
+        let cancelled = pool.commit_fallible(|| Ok::<_, String>(2));
+        cancelled.cancel().unwrap();
+        sender.send(()).unwrap();
+        assert_eq!(cancelled.wait(), Err(TaskError::Cancelled));
+
+        pool.wait_idle();
+        assert_eq!(pool.metrics().failed, 2);
+    }
+
+    #[test]
+    fn test_error_context() {
+        let pool = ThreadPool::new()
+            .num_threads(1)
+            .build()
+            .expect("Failed to create thread pool");
+
+        let (sender, receiver) = std::sync::mpsc::channel::<()>();
+        let (started_sender, started) = std::sync::mpsc::channel();
+        let running = pool.commit(Task::builder(move || {
+            started_sender.send(()).unwrap();
+            receiver.recv().unwrap();
+        }).label("gate").build());
+        started.recv().unwrap();
+        let queued = pool.commit(Task::builder(|| ()).label("resize").build());
+
+        // 运行中的任务无法取消, 错误中带有任务信息
+        let error = running.cancel().unwrap_err();
+        match &error {
+            Error::CancelAfterRunning(context) => {
+                assert_eq!(context.task_id, running.meta().id());
+                assert_eq!(context.label.as_deref(), Some("gate"));
+                assert_eq!(context.state, TaskState::Running);
+            },
+            other => panic!("unexpected error: {:?}", other),
+        }
+        assert!(error.to_string().contains("gate"));
+
+        queued.cancel().unwrap();
+        sender.send(()).unwrap();
+        let error = queued.wait().unwrap_err();
+        match &error {
+            Error::Cancelled(context) => {
+                assert_eq!(context.label.as_deref(), Some("resize"));
+                assert_eq!(context.state, TaskState::Cancelled);
+            },
+            other => panic!("unexpected error: {:?}", other),
+        }
+
+        // 可以通过 ? 转换为 Box<dyn Error>
+        fn wait_twice(handle: TaskHandle<()>) -> Result<(), Box<dyn std::error::Error>> {
+            handle.wait()?;
+            handle.wait()?;
+            Ok(())
+        }
+        assert_eq!(wait_twice(pool.commit(|| ())).unwrap_err().to_string(), "task already waited for");
+    }
+
+    // 模拟昂贵的线程资源, 例如数据库连接
+    struct Connection {
+        worker_id: usize,
+        queries: usize,
+    }
+
+    #[test]
+    fn test_worker_state() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let pool = ThreadPool::new()
+            .num_threads(2)
+            .worker_state(|worker_id| Connection { worker_id, queries: 0 })
+            .worker_state_teardown(move |worker_id, connection: Connection| {
+                sender.send((worker_id, connection.queries)).unwrap();
+            })
+            .build()
+            .expect("Failed to create thread pool");
+
+        let handles: Vec<_> = (0..10)
+            .map(|_| pool.commit_with_state(|connection: &mut Connection| {
+                connection.queries += 1;
+                connection.worker_id
+            }))
+            .collect();
+        for handle in handles {
+            assert!(handle.wait().unwrap() < 2);
+        }
+
+        // 类型不符的状态直接报错
+        let handle = pool.commit_with_state(|count: &mut usize| *count);
+        assert_eq!(handle.wait(), Err(Error::NoWorkerState));
+
+        // 每个工作线程退出时各自清理一次状态
+        drop(pool);
+        let mut teardowns: Vec<_> = receiver.iter().collect();
+        teardowns.sort();
+        assert_eq!(teardowns.iter().map(|(id, _)| *id).collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(teardowns.iter().map(|(_, queries)| queries).sum::<usize>(), 10);
+    }
+
+    #[test]
+    fn test_queued_task_dropped_with_pool() {
+        let pools: Vec<Box<dyn Fn() -> ThreadPoolBuilder>> = vec![
+            Box::new(|| ThreadPool::new().scheduler(FifoScheduler::new())),
+            Box::new(|| ThreadPool::new().scheduler(FairScheduler::new())),
+            Box::new(|| ThreadPool::new().scheduler(EdfScheduler::new())),
+        ];
+        for new_pool in pools {
+            let pool = new_pool().num_threads(1).build().expect("Failed to create thread pool");
+            let (sender, receiver) = std::sync::mpsc::channel::<()>();
+            let (started_sender, started) = std::sync::mpsc::channel();
+            pool.commit(move || {
+                started_sender.send(()).unwrap();
+                receiver.recv().ok();
+            });
+            started.recv().unwrap();
+
+            // 排队中的任务捕获了一个 Arc
+            let captured = Arc::new(());
+            let captured_clone = captured.clone();
+            let queued = pool.commit(move || drop(captured_clone));
+            assert_eq!(Arc::strong_count(&captured), 2);
+
+            // 线程池开始销毁之后才放行正在运行的任务, 排队的任务不会再运行
+            let release = thread::spawn(move || {
+                thread::sleep(Duration::from_millis(100));
+                sender.send(()).unwrap();
+            });
+            drop(pool);
+            release.join().unwrap();
+            // 线程池销毁后闭包也应被释放
+            assert_eq!(Arc::strong_count(&captured), 1);
+            drop(queued);
+        }
+    }
+
+    #[test]
+    fn test_worker_state_unavailable() {
+        let pool = Arc::new(ThreadPool::new()
+            .num_threads(1)
+            .worker_state(|_| 0usize)
+            .build()
+            .expect("Failed to create thread pool"));
+
+        // join 期间取到的另一个状态任务拿不到状态, 应报错而不是 panic
+        let pool_clone = pool.clone();
+        let outer = pool.commit_with_state(move |count: &mut usize| {
+            *count += 1;
+            let inner = pool_clone.commit_with_state(|count: &mut usize| *count);
+            let (_, b) = pool_clone.join(|| (), || 2);
+            (inner.wait(), b)
+        });
+        assert_eq!(outer.wait(), Ok((Err(Error::NoWorkerState), Ok(2))));
+
+        // 队列已满时由调用线程执行, 调用线程没有工作线程状态
+        let pool = ThreadPool::new()
+            .num_threads(1)
+            .queue_capacity(1)
+            .rejection_policy(RejectionPolicy::CallerRuns)
+            .worker_state(|_| 1usize)
+            .build()
+            .expect("Failed to create thread pool");
+        let (sender, receiver) = std::sync::mpsc::channel::<()>();
+        let (started_sender, started) = std::sync::mpsc::channel();
+        pool.commit(move || {
+            started_sender.send(()).unwrap();
+            receiver.recv().unwrap();
+        });
+        started.recv().unwrap();
+        let queued = pool.commit_with_state(|count: &mut usize| *count);
+        let rejected = pool.commit_with_state(|count: &mut usize| *count);
+        assert_eq!(rejected.wait(), Err(Error::NoWorkerState));
+        sender.send(()).unwrap();
+        assert_eq!(queued.wait(), Ok(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "backoff factor must be finite and at least 1.")]
+    fn test_retry_policy_rejects_negative_factor() {
+        // 负的倍数会产生负的等待时间
+        RetryPolicy::<String>::new(3).backoff(Backoff::Exponential {
+            initial: Duration::from_millis(10),
+            factor: -2.0,
+            max: Duration::from_secs(1),
+        });
+    }
+
+    #[test]
+    fn test_wait_on_blocking_task_from_worker() {
+        let pool = Arc::new(ThreadPool::new()
+            .num_threads(1)
+            .build()
+            .expect("Failed to create thread pool"));
+
+        // 阻塞任务由阻塞线程执行, 唯一的 worker 等待它不会死锁
+        let pool_clone = pool.clone();
+        let handle = pool.commit(move || {
+            pool_clone.commit_blocking(|| {
+                thread::sleep(Duration::from_millis(50)); // 模拟文件 I/O
+                7
+            }).wait()
+        });
+        assert_eq!(handle.wait(), Ok(Ok(7)));
+    }
+
+    #[test]
+    fn test_blocking_task_panics() {
+        let pool = ThreadPool::new()
+            .num_threads(1)
+            .max_blocking_threads(1)
+            .build()
+            .expect("Failed to create thread pool");
+
+        let handle = pool.commit_blocking(|| -> i32 { panic!("blocking task panicked!") });
+        assert!(matches!(handle.wait(), Err(Error::ChannelDisconnected(_))));
+
+        // 唯一的阻塞线程在任务 panic 后仍可继续使用
+        assert_eq!(pool.commit_blocking(|| 1).wait(), Ok(1));
+        assert_eq!(pool.metrics().blocking_workers, 1);
+    }
+
+    #[test]
+    fn test_join_with_custom_scheduler() {
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let pool = Arc::new(ThreadPool::new()
+            .num_threads(1)
+            .scheduler(RecordingScheduler { inner: FifoScheduler::new(), seen: seen.clone() })
+            .build()
+            .expect("Failed to create thread pool"));
+
+        // 唯一的 worker 在 join 中通过自定义调度器取到 b
+        let pool_clone = pool.clone();
+        let handle = pool.commit(move || pool_clone.join(|| 1, || 2));
+        assert_eq!(handle.wait(), Ok((1, Ok(2))));
+        assert_eq!(seen.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_timed_task_panics() {
+        let pool = ThreadPool::new()
+            .num_threads(2)
+            .build()
+            .expect("Failed to create thread pool");
+
+        // panic 立即可见, 不必等到超时
+        let start = std::time::Instant::now();
+        let handle = pool.commit_with_timeout(Duration::from_secs(3), || -> i32 { panic!("timed task panicked!") });
+        assert!(matches!(handle.wait(), Err(Error::ChannelDisconnected(_))));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_wait_idle_counts_delayed_tasks() {
+        let pool = ThreadPool::new()
+            .num_threads(1)
+            .build()
+            .expect("Failed to create thread pool");
+
+        let delayed = pool.commit_after(Duration::from_millis(50), || thread::sleep(Duration::from_millis(100)));
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let attempts_clone = attempts.clone();
+        let policy = RetryPolicy::new(2).backoff(Backoff::Fixed(Duration::from_millis(100)));
+        let retried = pool.commit_with_retry(policy, move || {
+            match attempts_clone.fetch_add(1, Ordering::SeqCst) {
+                0 => Err("first attempt failed".to_string()),
+                n => Ok(n),
+            }
+        });
+
+        // 尚未到期的任务和等待重试的任务都算作未完成
+        pool.wait_idle();
+        assert_eq!(delayed.state(), TaskState::Completed);
+        assert_eq!(retried.state(), TaskState::Completed);
+        assert_eq!(retried.attempts(), 2);
+
+        // 到期的任务同样受队列容量限制
+        let (pool, release, queued) = saturated_pool(RejectionPolicy::Abort);
+        let rejected = pool.commit_after(Duration::from_millis(10), || 2);
+        assert_eq!(rejected.wait(), Err(Error::Rejected));
+        release.send(()).unwrap();
+        assert_eq!(queued.wait(), Ok(1));
+    }
+
+    #[test]
+    fn test_rate_limit_with_discard_oldest() {
+        let pool = ThreadPool::new()
+            .num_threads(1)
+            .layer(RateLimitLayer::new(2.0, 1))
+            .queue_capacity(1)
+            .rejection_policy(RejectionPolicy::DiscardOldest)
+            .build()
+            .expect("Failed to create thread pool");
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let (started_sender, started_receiver) = std::sync::mpsc::channel();
+        let gate = pool.commit(move || {
+            started_sender.send(()).unwrap();
+            receiver.recv().unwrap();
+        });
+        started_receiver.recv().unwrap();
+        let queued = pool.commit(|| 1);
+
+        // 没有可用的令牌时不等待令牌, 也不丢弃排队的任务
+        let start = std::time::Instant::now();
+        let committed = pool.commit(|| 2);
+        assert!(start.elapsed() < Duration::from_millis(100));
+
+        sender.send(()).unwrap();
+        assert_eq!(gate.wait(), Ok(()));
+        assert_eq!(queued.wait(), Ok(1));
+        assert_eq!(committed.wait(), Ok(2));
+    }
+}